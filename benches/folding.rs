@@ -0,0 +1,58 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+// 维度折叠基准：顺序重建 vs ParallelFolder
+// 运行: cargo bench --bench folding
+// 环境变量: HTP_BENCH_ENTRIES (默认 1,000,000), HTP_BENCH_WORKERS (默认 0 = 全部 CPU)
+
+use std::time::Instant;
+use rug::Integer;
+use htp_core::core::affine::AffineTuple;
+use htp_core::core::algebra::ClassGroupElement;
+use htp_core::core::param::{SecurityProfile, SystemParameters};
+use htp_core::topology::folding::ParallelFolder;
+use htp_core::topology::tensor::HyperTensor;
+use htp_core::storage::store::CellRecord;
+
+fn env_or(key: &str, default: usize) -> usize {
+    std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let entries = env_or("HTP_BENCH_ENTRIES", 1_000_000);
+    let workers = env_or("HTP_BENCH_WORKERS", 0);
+
+    let params = SystemParameters::from_profile(b"htp-bench-folding", SecurityProfile::Test1024);
+    let d = params.discriminant;
+
+    // 单元格取固定调色板，只衡量遍历 + 节点合并开销
+    let g = ClassGroupElement::generator(&d);
+    let palette: Vec<AffineTuple> = (1..=64u32)
+        .map(|k| AffineTuple {
+            p_factor: Integer::from(1),
+            q_shift: g.pow(&Integer::from(k), &d).expect("generator power"),
+        })
+        .collect();
+
+    let mut tensor = HyperTensor::new(4, 100, d.clone());
+    for i in 0..entries {
+        let key = tensor.pack(&tensor.map_id_to_coord_hash(&format!("bench-{}", i)));
+        let value = palette[i % palette.len()].clone();
+        tensor.data.put(key, CellRecord { value, members: Vec::new() }).expect("store put");
+    }
+    println!("📊 Folding benchmark: {} cells (d=4, L=100, 1024-bit Δ)", tensor.data.len());
+
+    let t = Instant::now();
+    let sequential = tensor.compute_root_internal().expect("sequential fold");
+    let t_seq = t.elapsed();
+    println!("   rebuild (sequential):        {:>10.2?}", t_seq);
+
+    let folder = ParallelFolder::new(workers).expect("thread pool");
+    let t = Instant::now();
+    let parallel = folder.fold(&tensor).expect("parallel fold");
+    let t_par = t.elapsed();
+    println!("   ParallelFolder ({:>2} workers): {:>10.2?}", folder.workers(), t_par);
+
+    assert_eq!(sequential.p_factor, parallel.p_factor, "parallel fold diverged from sequential fold");
+    assert_eq!(sequential.q_shift, parallel.q_shift, "parallel fold diverged from sequential fold");
+    println!("   speedup: {:.2}x", t_seq.as_secs_f64() / t_par.as_secs_f64());
+}
@@ -3,7 +3,16 @@
 use clap::{Parser, Subcommand};
 use log::{info, error, debug};
 use htp_core::net::transport::QuicTransport;
-use htp_core::net::wire::{HtpRequest, HtpResponse, RequestHeader};
+use htp_core::net::wire::{HtpRequest, HtpResponse, OperatorAuth, RequestHeader, PROTOCOL_VERSION};
+use htp_core::topology::consistency::verify_consistency;
+use htp_core::topology::witness::WitnessStore;
+use htp_core::topology::tensor::index_to_coord;
+use htp_core::core::affine::AffineTuple;
+use htp_core::core::primes::{PrimeHashConfig, PrimeHashFunction};
+use htp_core::core::certificate::{
+    certify_representative, verify_certificate, verify_discriminant_certificate,
+    DiscriminantCertificate, PrimalityEvidence,
+};
 use bincode::Options;
 use rug::Integer;
 use std::time::SystemTime;
@@ -13,6 +22,24 @@ use std::time::SystemTime;
 struct Cli {
     #[arg(short, long, default_value = "127.0.0.1:4433")]
     server: String,
+
+    /// Hash-to-Prime 哈希函数 (默认 blake3)
+    /// 联网命令采用节点公布的参数；显式指定的参数必须与节点一致，否则验证中止
+    #[arg(long)]
+    prime_hash: Option<PrimeHashFunction>,
+
+    /// Hash-to-Prime 域分离标签 (默认 htp:hash-to-prime:v1)
+    #[arg(long)]
+    prime_tag: Option<String>,
+
+    /// 素数代表元位长 (64-256，默认 128)
+    #[arg(long)]
+    prime_bits: Option<u32>,
+
+    /// 本地见证存储文件 (Verify 时写入，Sync 时增量更新)
+    #[arg(long, default_value = "htp_witnesses.bin")]
+    witness_store: String,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -20,8 +47,73 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     Verify { user_id: String },
-    Register { user_id: String },
+    Register {
+        user_id: String,
+        /// 已注册时以新记录替换旧记录 (默认幂等拒绝)
+        #[arg(long)]
+        reregister: bool,
+    },
     Root,
+    /// 拉取见证更新并在本地刷新所有已存储的见证
+    Sync,
+    /// 查询历史 epoch 的根
+    RootAt { epoch: u64 },
+    /// 校验两个 epoch 之间的一致性证明 (只追加、无静默删除)
+    Consistency { from: u64, to: u64 },
+    /// 批量注册：从文件读取用户 ID (每行一个)
+    RegisterBatch { path: String },
+    /// [Operator] 撤销成员
+    Revoke {
+        user_id: String,
+        /// 运维口令 (必须与节点一致，也可通过 HTP_OPERATOR_SECRET 环境变量设置)
+        #[arg(long)]
+        operator_secret: Option<String>,
+    },
+    /// [Operator] 在线重分片到新几何 (新 epoch 起生效，旧见证需重新获取)
+    Reshard {
+        #[arg(long)]
+        dimensions: u32,
+        #[arg(long)]
+        side_length: u64,
+        /// 运维口令 (必须与节点一致，也可通过 HTP_OPERATOR_SECRET 环境变量设置)
+        #[arg(long)]
+        operator_secret: Option<String>,
+    },
+    /// [Offline] 为用户的素数代表元生成并校验 Pocklington 证书
+    Certify { user_id: String },
+    /// [Offline] 校验节点导出的判别式证书
+    CheckCert {
+        path: String,
+        /// 待校验的判别式 (十进制，负数)
+        #[arg(long, allow_hyphen_values = true)]
+        discriminant: String,
+    },
+}
+
+impl Cli {
+    /// 以 base 为基础，覆盖命令行显式指定的 Hash-to-Prime 参数
+    fn prime_config(&self, base: &PrimeHashConfig) -> PrimeHashConfig {
+        PrimeHashConfig {
+            hash: self.prime_hash.unwrap_or(base.hash),
+            domain_tag: self.prime_tag.as_ref().map_or_else(|| base.domain_tag.clone(), |t| t.as_bytes().to_vec()),
+            bit_size: self.prime_bits.unwrap_or(base.bit_size),
+            primality: base.primality,
+        }
+    }
+
+    /// 节点公布的参数；与命令行显式指定的参数不一致时报错 (否则身份绑定校验会静默失败)
+    fn published_prime_config(&self, published: PrimeHashConfig) -> anyhow::Result<PrimeHashConfig> {
+        let requested = self.prime_config(&published);
+        if requested != published {
+            anyhow::bail!("Hash-to-Prime parameter mismatch: node uses {}, command line requested {}.",
+                describe_prime_config(&published), describe_prime_config(&requested));
+        }
+        Ok(published)
+    }
+}
+
+fn describe_prime_config(config: &PrimeHashConfig) -> String {
+    format!("{} / tag {:?} / {} bits", config.hash, String::from_utf8_lossy(&config.domain_tag), config.bit_size)
 }
 
 #[tokio::main]
@@ -29,91 +121,285 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
     let cli = Cli::parse();
 
-    let transport = QuicTransport::bind_client()?;
+    // 离线命令：无需连接服务器，也无需信任服务器
+    match &cli.command {
+        Commands::Certify { user_id } => {
+            let prime_config = cli.prime_config(&PrimeHashConfig::default());
+            prime_config.validate().map_err(|e| anyhow::anyhow!(e))?;
+            return certify_user(&prime_config, user_id);
+        },
+        Commands::CheckCert { path, discriminant } => return check_discriminant_cert(path, discriminant),
+        _ => {}
+    }
+
+    let transport = QuicTransport::bind_client().map_err(|e| anyhow::anyhow!(e))?;
     let endpoint = transport.get_endpoint();
     let server_addr: std::net::SocketAddr = cli.server.parse()?;
     
     info!("🔌 Connecting to HTP Node at {}...", server_addr);
     let connection = endpoint.connect(server_addr, "localhost")?.await?;
-    let (mut send, mut recv) = connection.open_bi().await?;
 
-    // 构造带时间戳的 Header，防止重放
-    let now = SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
-    let header = RequestHeader { version: 1, timestamp: now, request_id: 1 };
+    // [SECURITY FIX]: 运维标签绑定节点的部署标识，签名前先查询
+    let deployment = match &cli.command {
+        Commands::Revoke { .. } | Commands::Reshard { .. } => match exchange(&connection, &HtpRequest::GetGlobalRoot { header: new_header()? }).await? {
+            HtpResponse::GlobalRoot { deployment, .. } => deployment,
+            HtpResponse::Error(e) => anyhow::bail!("Failed to fetch the deployment identifier: {}", e),
+            _ => anyhow::bail!("Unexpected response while fetching the deployment identifier."),
+        },
+        _ => [0u8; 32],
+    };
+    let header = new_header()?;
 
     let request = match &cli.command {
         Commands::Verify { user_id } => HtpRequest::GetProof { 
             header,
             user_id: user_id.clone(), 
         },
-        Commands::Register { user_id } => HtpRequest::RegisterUser {
+        Commands::Register { user_id, reregister } => HtpRequest::RegisterUser {
             header,
             user_id: user_id.clone(),
+            reregister: *reregister,
+        },
+        Commands::RegisterBatch { path } => {
+            let user_ids: Vec<String> = std::fs::read_to_string(path)?
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect();
+            info!("📦 Submitting batch of {} users...", user_ids.len());
+            HtpRequest::RegisterBatch { header, user_ids }
         },
         Commands::Root => HtpRequest::GetGlobalRoot { header },
+        Commands::Sync => match load_witness_store(&cli.witness_store)?.epoch() {
+            Some(since_epoch) => HtpRequest::GetUpdates { header, since_epoch },
+            None => {
+                println!("ℹ️  No stored witnesses in {}. Run `verify` first.", cli.witness_store);
+                return Ok(());
+            }
+        },
+        Commands::RootAt { epoch } => HtpRequest::GetRootAt { header, epoch: *epoch },
+        Commands::Consistency { from, to } => HtpRequest::GetConsistencyProof { header, from: *from, to: *to },
+        Commands::Revoke { user_id, operator_secret } => {
+            let secret = operator_secret.clone()
+                .or_else(|| std::env::var("HTP_OPERATOR_SECRET").ok())
+                .ok_or_else(|| anyhow::anyhow!("Revocation requires --operator-secret or HTP_OPERATOR_SECRET."))?;
+            let operator_tag = OperatorAuth::from_secret(&secret).sign(&deployment, &header, user_id);
+            HtpRequest::RevokeUser { header, user_id: user_id.clone(), operator_tag }
+        },
+        Commands::Reshard { dimensions, side_length, operator_secret } => {
+            let secret = operator_secret.clone()
+                .or_else(|| std::env::var("HTP_OPERATOR_SECRET").ok())
+                .ok_or_else(|| anyhow::anyhow!("Re-sharding requires --operator-secret or HTP_OPERATOR_SECRET."))?;
+            let operator_tag = OperatorAuth::from_secret(&secret).sign_reshard(&deployment, &header, *dimensions, *side_length);
+            HtpRequest::Reshard { header, dimensions: *dimensions, side_length: *side_length, operator_tag }
+        },
+        Commands::Certify { .. } | Commands::CheckCert { .. } => unreachable!("offline commands are handled before connecting"),
     };
 
-    let req_bytes = bincode::serialize(&request)?;
-    send.write_all(&req_bytes).await?;
-    send.finish().await?;
-
-    let mut buf = vec![0u8; 8192];
-    let len = recv.read(&mut buf).await?.unwrap_or(0);
-
-    let safe_config = bincode::DefaultOptions::new()
-        .with_limit(5 * 1024 * 1024) 
-        .with_fixint_encoding()
-        .allow_trailing_bytes();
-
-    let response: HtpResponse = safe_config.deserialize(&buf[..len])?;
+    let response = exchange(&connection, &request).await?;
 
     match response {
-        HtpResponse::ProofBundle { primary_path, epoch, .. } => {
+        HtpResponse::ProofBundle { primary_path, prime_nonce, bucket_depth, bucket_chain, witness, epoch, index, prime_config, discriminant, .. } => {
             info!("📦 Received Proof Bundle (Epoch: {}).", epoch);
+            let prime_config = cli.published_prime_config(prime_config)?;
             
             if primary_path.is_empty() {
                 error!("❌ VERIFICATION FAILED: Proof path is empty.");
                 std::process::exit(1);
             }
-            
+
             // [SECURITY FIX]: 身份绑定校验 (Identity Binding Check)
             // 防止 Proof Binding Attack (冒充者攻击)
             if let Commands::Verify { user_id } = &cli.command {
                 info!("🕵️ Verifying User Identity binding...");
-                let expected_p = htp_core::core::primes::hash_to_prime(user_id, 64)
-                    .map_err(|e| anyhow::anyhow!("Local Prime Gen Failed: {}", e))?;
-
-                let leaf_node = &primary_path[0];
-                if leaf_node.p_factor != expected_p {
+                // [FIX]: 碰撞桶中叶子是多名成员的合成值，身份绑定在桶内成员链中自身的位置上校验
+                let member = match bucket_chain.get(bucket_depth as usize) {
+                    Some(m) => m,
+                    None => {
+                        error!("❌ VERIFICATION FAILED: Bucket depth {} outside chain of {}.", bucket_depth, bucket_chain.len());
+                        std::process::exit(1);
+                    }
+                };
+                // 按证明中的 nonce 重算单个候选数，只做一次素性测试 (不重放搜索，也不要求 nonce 为首个命中)
+                if !prime_config.verify_representative(user_id, prime_nonce, &member.p_factor) {
                     // 如果这是 Dummy Proof，这里也会校验失败，从侧面保护了隐私
                     error!("❌ SPOOFING DETECTED: Proof belongs to a different user!");
                     std::process::exit(1);
                 }
-                info!("✅ Identity Confirmed.");
-            }
 
-            // 执行数学验证
-            debug!("🔄 Recomputing Affine Path...");
-            let mut calculated_agg = primary_path[0].clone();
-            let one = Integer::from(1);
-            let four = Integer::from(4);
-            // 简单的 Discriminant 提取 hack
-            let discriminant = &one - (&primary_path[0].q_shift.c * &four); 
-
-            for i in 1..primary_path.len() {
-                calculated_agg = calculated_agg.compose(&primary_path[i], &discriminant)
-                    .map_err(|e| anyhow::anyhow!("Math Error: {}", e))?;
+                // 桶内成员链按序合成必须等于叶子 (判别式取自节点公布的参数)
+                let mut bucket_agg = bucket_chain[0].clone();
+                for sibling in &bucket_chain[1..] {
+                    bucket_agg = bucket_agg.compose(sibling, &discriminant)
+                        .map_err(|e| anyhow::anyhow!("Math Error: {}", e))?;
+                }
+                if bucket_agg.p_factor != primary_path[0].p_factor || bucket_agg.q_shift != primary_path[0].q_shift {
+                    error!("❌ VERIFICATION FAILED: Bucket chain does not fold to the proven leaf.");
+                    std::process::exit(1);
+                }
+                info!("✅ Identity Confirmed (bucket depth {} of {}).", bucket_depth, bucket_chain.len());
+
+                // [NEW]: 顺序寻址 - 坐标必须由证明中的序号按 v_k = (i // L^{k-1}) mod L 复算得到
+                if let Some(i) = index {
+                    if index_to_coord(i, witness.coord.len(), witness.side_length) != witness.coord {
+                        error!("❌ VERIFICATION FAILED: Coordinate {:?} does not match sequential index {}.", witness.coord, i);
+                        std::process::exit(1);
+                    }
+                    info!("✅ Sequential index {} maps to coordinate {:?}.", i, witness.coord);
+                }
+
+                // [FIX]: 见证必须携带同一条成员链，并由兄弟节点重建出证明末项的根
+                if witness.depth != bucket_depth || !same_chain(&witness.bucket_chain, &bucket_chain) {
+                    error!("❌ VERIFICATION FAILED: Witness does not carry the proven bucket chain.");
+                    std::process::exit(1);
+                }
+                debug!("🔄 Recomputing root from witness...");
+                let root = primary_path.last().expect("non-empty path");
+                let rebuilt = witness.compute_root(&discriminant).map_err(|e| anyhow::anyhow!("Math Error: {}", e))?;
+                if rebuilt.p_factor != root.p_factor || rebuilt.q_shift != root.q_shift {
+                    error!("❌ VERIFICATION FAILED: Witness does not reproduce the root of epoch {}.", epoch);
+                    std::process::exit(1);
+                }
+
+                // 保存见证，之后通过 Sync 在本地随新 epoch 更新
+                let mut store = load_witness_store(&cli.witness_store)?;
+                store.insert(user_id, witness);
+                save_witness_store(&cli.witness_store, &store)?;
+                debug!("💾 Witness stored in {}.", cli.witness_store);
+                println!("✅ Proof Verified: Epoch {} Root Hash = {:x}", epoch, root.p_factor);
             }
-            println!("✅ Proof Verified: Path Aggregate P-Factor = {:x}...", calculated_agg.p_factor);
         },
-        HtpResponse::GlobalRoot(root) => {
+        HtpResponse::GlobalRoot { root, prime_config, .. } => {
             println!("🌳 Global Root Hash: {:x}", root.p_factor);
+            println!("🔢 Hash-to-Prime: {}", describe_prime_config(&prime_config));
+            cli.published_prime_config(prime_config)?;
+        },
+        HtpResponse::RootAt(record) => {
+            println!("🌳 Epoch {} Root Hash: {:x} ({} changes, committed at {})",
+                record.epoch, record.root.p_factor, record.changes, record.timestamp);
+        },
+        HtpResponse::Updates { discriminant, updates } => {
+            let mut store = load_witness_store(&cli.witness_store)?;
+            let Some(last) = updates.last() else {
+                println!("✅ Witnesses already up to date.");
+                return Ok(());
+            };
+            let revoked = store.apply_updates(&updates, &discriminant).map_err(|e| anyhow::anyhow!(e))?;
+            for user_id in &revoked {
+                println!("⚠️  '{}' is no longer a member; witness dropped.", user_id);
+            }
+            save_witness_store(&cli.witness_store, &store)?;
+            println!("✅ Applied {} update(s). Witnesses now at epoch {}.", updates.len(), last.epoch);
+        },
+        HtpResponse::Consistency { from_root, to_root, proof, discriminant } => {
+            // 实际部署中 from_root 应与客户端本地缓存的旧根比对
+            match verify_consistency(&from_root.root, &to_root.root, &proof, &discriminant) {
+                Ok(report) if report.is_append_only() => {
+                    println!("✅ Epoch {} -> {} consistent: append-only ({} new members).", proof.from, proof.to, report.appended);
+                },
+                Ok(report) => {
+                    println!("⚠️  Epoch {} -> {} consistent, but {} cell(s) were rewritten by revocation/re-registration ({} appended).",
+                        proof.from, proof.to, report.rewritten_cells, report.appended);
+                },
+                Err(e) => {
+                    error!("❌ CONSISTENCY FAILED: {}", e);
+                    std::process::exit(1);
+                },
+            }
+        },
+        HtpResponse::RegisterSuccess { epoch, index, .. } => match index {
+            Some(i) => println!("✅ User Registered Successfully (Epoch: {}, Index: {})", epoch, i),
+            None => println!("✅ User Registered Successfully (Epoch: {})", epoch),
+        },
+        HtpResponse::AlreadyRegistered { epoch, .. } => {
+            println!("ℹ️  User already registered (Epoch: {}). Use --reregister to replace the existing record.", epoch);
         },
-        HtpResponse::RegisterSuccess { epoch, .. } => {
-            println!("✅ User Registered Successfully (Epoch: {})", epoch);
+        HtpResponse::BatchRegistered { epoch, registered, already_registered, .. } => {
+            println!("✅ Batch committed (Epoch: {}): {} registered, {} already present.", epoch, registered, already_registered);
+        },
+        HtpResponse::RevokeSuccess { epoch, .. } => {
+            println!("🗑️ User Revoked (New Epoch: {})", epoch);
+        },
+        HtpResponse::Resharded { epoch, dimensions, side_length, .. } => {
+            println!("🔀 Re-sharded to {}^{} (New Epoch: {}). Run `verify` again to refresh stored witnesses.", side_length, dimensions, epoch);
         },
         HtpResponse::Error(e) => error!("Server Error: {}", e),
     }
 
     Ok(())
 }
+
+/// 构造带时间戳的 Header，防止重放；request_id 随机选取 (节点拒绝窗口内重复的运维 request_id)
+fn new_header() -> anyhow::Result<RequestHeader> {
+    let now = SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+    let mut id = [0u8; 8];
+    getrandom::getrandom(&mut id).map_err(|e| anyhow::anyhow!("Failed to generate request id: {}", e))?;
+    Ok(RequestHeader { version: PROTOCOL_VERSION, timestamp: now, request_id: u64::from_le_bytes(id) })
+}
+
+/// 在新的双向流上发送一个请求并读取响应
+async fn exchange(connection: &quinn::Connection, request: &HtpRequest) -> anyhow::Result<HtpResponse> {
+    let (mut send, mut recv) = connection.open_bi().await?;
+    let req_bytes = bincode::serialize(request)?;
+    send.write_all(&req_bytes).await?;
+    send.finish().await?;
+
+    // [FIX]: 一致性证明等响应可能超过单次读取的 8 KiB，读到流结束 (带上限)
+    let buf = recv.read_to_end(5 * 1024 * 1024).await?;
+
+    let safe_config = bincode::DefaultOptions::new()
+        .with_limit(5 * 1024 * 1024) 
+        .with_fixint_encoding()
+        .allow_trailing_bytes();
+
+    Ok(safe_config.deserialize(&buf)?)
+}
+
+fn same_chain(x: &[AffineTuple], y: &[AffineTuple]) -> bool {
+    x.len() == y.len() && x.iter().zip(y).all(|(a, b)| a.p_factor == b.p_factor && a.q_shift == b.q_shift)
+}
+
+fn load_witness_store(path: &str) -> anyhow::Result<WitnessStore> {
+    if !std::path::Path::new(path).exists() {
+        return Ok(WitnessStore::default());
+    }
+    Ok(bincode::deserialize(&std::fs::read(path)?)?)
+}
+
+fn save_witness_store(path: &str, store: &WitnessStore) -> anyhow::Result<()> {
+    std::fs::write(path, bincode::serialize(store)?)?;
+    Ok(())
+}
+
+fn certify_user(prime_config: &PrimeHashConfig, user_id: &str) -> anyhow::Result<()> {
+    let rep = prime_config.hash_to_prime(user_id)
+        .map_err(|e| anyhow::anyhow!("Local Prime Gen Failed: {}", e))?;
+    info!("🔢 Representative: {} (nonce {})", rep.prime, rep.nonce);
+
+    let cert = certify_representative(&rep).map_err(|e| anyhow::anyhow!(e))?;
+    verify_certificate(&cert).map_err(|e| anyhow::anyhow!(e))?;
+    println!("✅ Primality Certified (deterministic): {}", rep.prime);
+    Ok(())
+}
+
+fn check_discriminant_cert(path: &str, discriminant: &str) -> anyhow::Result<()> {
+    let discriminant: Integer = discriminant.parse()
+        .map_err(|e| anyhow::anyhow!("Invalid discriminant: {}", e))?;
+    let bytes = std::fs::read(path)?;
+    let cert: DiscriminantCertificate = bincode::deserialize(&bytes)?;
+
+    match verify_discriminant_certificate(&discriminant, &cert) {
+        Ok(PrimalityEvidence::Proven) => {
+            println!("✅ Discriminant derivation verified ({} steps). Primality PROVEN.", cert.steps);
+        },
+        Ok(PrimalityEvidence::Probabilistic) => {
+            println!("✅ Discriminant derivation verified ({} steps). Primality: Baillie-PSW (probabilistic).", cert.steps);
+        },
+        Err(e) => {
+            error!("❌ CERTIFICATE REJECTED: {}", e);
+            std::process::exit(1);
+        },
+    }
+    Ok(())
+}
@@ -1,68 +1,269 @@
 // COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
 
 use clap::Parser;
-use log::{info, error};
+use log::{info, warn, error};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::net::SocketAddr;
 
-use htp_core::core::param::SystemParameters;
+use htp_core::core::param::{SecurityProfile, SystemParameters};
+use htp_core::core::certificate::certify_discriminant;
+use htp_core::core::primes::PrimeHashFunction;
 use htp_core::topology::tensor::HyperTensor;
+use htp_core::topology::placement::{AddressingMode, FullCellPolicy};
+use htp_core::topology::snapshot::EpochSnapshots;
 use htp_core::net::transport::QuicTransport;
-use htp_core::net::service::run_prover_service;
+use htp_core::net::service::{run_prover_service, ReplayCache, ServiceContext};
+use htp_core::net::compute::{ComputePool, SingleFlight};
+use htp_core::net::config::NodeConfig;
+use htp_core::storage::Persistence;
+use htp_core::storage::group_commit::GroupCommitter;
+use htp_core::storage::store::{CellStore, LogStore, StoreBackend};
+use htp_core::net::wire::OperatorAuth;
 
 #[derive(Parser)]
 #[command(name = "HTP Node")]
 struct Cli {
-    #[arg(short, long, default_value = "127.0.0.1:4433")]
-    bind: String,
+    /// TOML 配置文件 (包含下列全部参数)；命令行参数优先于配置文件
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    #[arg(short, long)]
+    bind: Option<String>,
+
+    #[arg(short, long)]
+    seed: Option<String>,
+
+    #[arg(short, long)]
+    dim: Option<usize>,
 
+    /// 张量边长 L (仅在创建新张量时生效；L^d 不得超出 128-bit 坐标空间)
+    #[arg(long)]
+    side_length: Option<usize>,
+
+    /// 安全档位: test-1024 | standard-2048 | high-3072
     #[arg(short, long)]
-    seed: String,
+    profile: Option<SecurityProfile>,
+
+    /// 判别式位长，覆盖安全档位 (仅在创建新张量时生效)
+    #[arg(long)]
+    discriminant_bits: Option<u32>,
+
+    /// Hash-to-Prime 哈希函数 (仅在创建新张量时生效)
+    #[arg(long)]
+    prime_hash: Option<PrimeHashFunction>,
+
+    /// Hash-to-Prime 域分离标签 (仅在创建新张量时生效)
+    #[arg(long)]
+    prime_tag: Option<String>,
+
+    /// 素数代表元位长 64-256 (仅在创建新张量时生效)
+    #[arg(long)]
+    prime_bits: Option<u32>,
+
+    /// 创建新张量时导出判别式证书 (供审计方离线校验)
+    #[arg(long)]
+    discriminant_cert: Option<PathBuf>,
+
+    /// 寻址方式: hashed | sequential (仅在创建新张量时生效；sequential 在注册时依次分配数字序号)
+    #[arg(long)]
+    addressing: Option<AddressingMode>,
 
-    #[arg(short, long, default_value_t = 4)]
-    dim: usize,
+    /// 使用公开 (无密钥) 的坐标映射 (仅在创建新张量时生效；默认生成部署级坐标密钥)
+    #[arg(long)]
+    no_coord_key: bool,
+
+    /// 每个单元格的成员上限，0 表示按 P-factor 上限推导 (仅在创建新张量时生效)
+    #[arg(long)]
+    max_cell_members: Option<u32>,
+
+    /// 单元格已满时: reject | probe (仅在创建新张量时生效)
+    #[arg(long)]
+    full_cell: Option<FullCellPolicy>,
+
+    /// 单元格存储后端: memory | log (仅在创建新张量时生效；log 可服务大于内存的张量)
+    #[arg(long)]
+    store: Option<StoreBackend>,
+
+    /// 数据目录 (快照、WAL 与单元格日志)
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// 每累积多少条 WAL 记录写一次快照
+    #[arg(long)]
+    snapshot_interval: Option<u64>,
+
+    /// 组提交：首个注册入队后最多等待多少毫秒凑批再 fsync
+    #[arg(long)]
+    commit_delay_ms: Option<u64>,
+
+    /// 组提交：单次 fsync 最多合并的请求数
+    #[arg(long)]
+    commit_batch: Option<usize>,
+
+    /// 计算池并发数 (折叠与证明构造)；0 表示逻辑 CPU 数
+    #[arg(long)]
+    compute_workers: Option<usize>,
+
+    /// 运维口令，用于授权 RevokeUser 等管理请求 (也可通过 HTP_OPERATOR_SECRET 环境变量设置)
+    #[arg(long)]
+    operator_secret: Option<String>,
+}
+
+impl Cli {
+    /// 配置文件 (若有) 打底，命令行逐项覆盖
+    fn resolve(self) -> Result<NodeConfig, String> {
+        let mut config = match &self.config {
+            Some(path) => NodeConfig::load(path)?,
+            None => NodeConfig::default(),
+        };
+        macro_rules! overlay {
+            ($($field:ident),*) => { $(if let Some(v) = self.$field { config.$field = v; })* };
+        }
+        overlay!(bind, dim, side_length, profile, prime_hash, prime_tag, prime_bits, addressing, max_cell_members, full_cell, store,
+            data_dir, snapshot_interval, commit_delay_ms, commit_batch, compute_workers);
+        if self.no_coord_key {
+            config.keyed_coords = false;
+        }
+        config.seed = self.seed.or(config.seed);
+        config.discriminant_bits = self.discriminant_bits.or(config.discriminant_bits);
+        config.discriminant_cert = self.discriminant_cert.or(config.discriminant_cert);
+        config.operator_secret = self.operator_secret.or(config.operator_secret);
+        config.validate()?;
+        Ok(config)
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
-    let cli = Cli::parse();
-
-    // [SECURITY FIX]: 输入参数校验，防止零维黑洞 (Zero-Dimension Singularity)
-    if cli.dim == 0 || cli.dim > 20 {
-        error!("❌ Invalid dimension. Must be between 1 and 20.");
-        std::process::exit(1);
-    }
+    let config = match Cli::parse().resolve() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("❌ Invalid configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let prime_config = config.prime_config().expect("validated");
 
     info!("🚀 Initializing HTP Node (Secure Edition)...");
+    if config.discriminant_bits() < 2048 {
+        warn!("⚠️  {}-bit discriminant is for testing only. Do NOT use in production.", config.discriminant_bits());
+    }
 
     // [FIX]: 健忘节点修复 - 启用持久化加载
-    let db_path = "htp_tensor.db";
-    let tensor = if std::path::Path::new(db_path).exists() {
-        info!("💾 Found existing database. Loading...");
-        match HyperTensor::load_from_disk(db_path) {
-            Ok(t) => {
-                info!("✅ Database loaded successfully.");
-                Arc::new(RwLock::new(t))
-            },
-            Err(e) => {
-                error!("❌ Failed to load database: {}. Starting fresh.", e);
-                let params = SystemParameters::from_random_seed(cli.seed.as_bytes(), 2048);
-                Arc::new(RwLock::new(HyperTensor::new(cli.dim, 100, params.discriminant)))
+    // [FIX]: 快照 + WAL 崩溃恢复；数据库损坏时拒绝启动，而不是用新判别式 "重新开始" 覆盖旧数据
+    if let Err(e) = std::fs::create_dir_all(&config.data_dir) {
+        error!("❌ Failed to create data directory {}: {}", config.data_dir.display(), e);
+        std::process::exit(1);
+    }
+    let (mut persistence, recovered) = match Persistence::open(&config.snapshot_path(), &config.wal_path()) {
+        Ok((mut p, t)) => {
+            p.snapshot_interval = config.snapshot_interval;
+            (p, t)
+        },
+        Err(e) => {
+            error!("❌ Failed to recover database: {}. Refusing to start; move the files aside to start fresh.", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut tensor = match recovered {
+        Some(t) => {
+            info!("✅ Database recovered (epoch {}, {} cells, {} store).", t.epoch, t.data.len(), t.data.backend());
+            if t.prime_config != prime_config {
+                warn!("⚠️  Stored hash-to-prime config ({}, {} bits) overrides command line.", t.prime_config.hash, t.prime_config.bit_size);
+            }
+            if (t.dimensions, t.side_length) != (config.dim, config.side_length) {
+                warn!("⚠️  Stored geometry {}^{} overrides configured {}^{}.", t.side_length, t.dimensions, config.side_length, config.dim);
             }
+            if t.addressing != config.addressing {
+                warn!("⚠️  Stored addressing mode ({}) overrides configured {}.", t.addressing, config.addressing);
+            }
+            if t.addressing == AddressingMode::Hashed && !t.placement.is_keyed() {
+                warn!("⚠️  Coordinate mapping is unkeyed: cells can be targeted by grinding user IDs.");
+            }
+            t
+        },
+        None => {
+            info!("✨ Creating new Hyper-Tensor.");
+            let params = generate_parameters(&config);
+            let store = match config.store {
+                StoreBackend::Memory => CellStore::default(),
+                StoreBackend::Log => match LogStore::create(&config.cells_dir()) {
+                    Ok(s) => CellStore::new(s),
+                    Err(e) => {
+                        error!("❌ Failed to create cell store: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+            };
+            let mut t = HyperTensor::new_with_store(config.dim, config.side_length, params.discriminant, prime_config.clone(), store);
+            t.addressing = config.addressing;
+            t.placement = match config.placement() {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            };
+            // 立即写入基础快照，固定系统参数
+            if let Err(e) = persistence.snapshot(&mut t) {
+                error!("❌ Failed to write initial snapshot: {}", e);
+                std::process::exit(1);
+            }
+            t
         }
-    } else {
-        info!("✨ Creating new Hyper-Tensor.");
-        let params = SystemParameters::from_random_seed(cli.seed.as_bytes(), 2048);
-        Arc::new(RwLock::new(HyperTensor::new(cli.dim, 100, params.discriminant)))
     };
+    // 首个只读快照：启动时折叠一次，之后每个 epoch 增量发布
+    let snapshots = match EpochSnapshots::new(&mut tensor) {
+        Ok(s) => Arc::new(s),
+        Err(e) => {
+            error!("❌ Failed to build the initial snapshot: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let tensor = Arc::new(RwLock::new(tensor));
+
+    let operator = config.operator_secret.clone()
+        .or_else(|| std::env::var("HTP_OPERATOR_SECRET").ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| OperatorAuth::from_secret(&s));
+    if operator.is_none() {
+        warn!("⚠️  No operator secret configured. Revocation and re-sharding requests will be rejected.");
+    }
 
-    let addr: SocketAddr = cli.bind.parse()?;
-    let transport = QuicTransport::bind_server(addr, "cert.pem", "key.pem").await?;
+    let addr: SocketAddr = config.bind.parse()?;
+    let transport = QuicTransport::bind_server(addr, "cert.pem", "key.pem").await.map_err(|e| anyhow::anyhow!(e))?;
     
     info!("📡 QUIC Transport listening on {}", addr);
-    run_prover_service(transport.get_endpoint().clone(), tensor).await;
+    // [PERF FIX]: 磁盘写入移出张量写锁，由后台任务组提交
+    let committer = GroupCommitter::spawn(persistence, tensor.clone(), snapshots.clone(), config.group_commit());
+    let ctx = ServiceContext {
+        operator,
+        committer: Some(committer),
+        snapshots,
+        compute: ComputePool::new(config.compute_workers),
+        proofs: SingleFlight::new(),
+        operator_requests: ReplayCache::new(),
+    };
+    run_prover_service(transport.get_endpoint().clone(), tensor, ctx).await;
 
     Ok(())
 }
+
+fn generate_parameters(config: &NodeConfig) -> SystemParameters {
+    let params = SystemParameters::from_random_seed(config.seed().as_bytes(), config.discriminant_bits());
+
+    if let Some(path) = &config.discriminant_cert {
+        info!("📜 Exporting discriminant certificate to {}...", path.display());
+        let exported = certify_discriminant(config.seed().as_bytes(), config.discriminant_bits())
+            .and_then(|cert| bincode::serialize(&cert).map_err(|e| e.to_string()))
+            .and_then(|bytes| std::fs::write(path, bytes).map_err(|e| e.to_string()));
+        if let Err(e) = exported {
+            error!("❌ Failed to export discriminant certificate: {}", e);
+        }
+    }
+    params
+}
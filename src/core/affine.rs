@@ -3,6 +3,9 @@
 use super::algebra::ClassGroupElement;
 use rug::Integer;
 
+/// 合成后 P-factor 的位长上限
+pub const MAX_P_FACTOR_BITS: u32 = 4096;
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct AffineTuple {
     pub p_factor: Integer,      
@@ -21,8 +24,8 @@ impl AffineTuple {
         // [SECURITY FIX]: 限制 P-factor 大小为 4096 bits (常规 RSA 级别)
         // 防止 CPU DoS 和 存储桶堵塞攻击的先决条件
         let p_bits = self.p_factor.significant_bits() + other.p_factor.significant_bits();
-        if p_bits > 4096 { 
-             return Err(format!("❌ Security Halt: Affine P-Factor size ({} bits) exceeds safety limit (4096).", p_bits));
+        if p_bits > MAX_P_FACTOR_BITS { 
+             return Err(format!("❌ Security Halt: Affine P-Factor size ({} bits) exceeds safety limit ({}).", p_bits, MAX_P_FACTOR_BITS));
         }
 
         let new_p = Integer::from(&self.p_factor * &other.p_factor);
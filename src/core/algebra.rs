@@ -1,6 +1,6 @@
 // COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
 
-use rug::{Integer, ops::Pow};
+use rug::{Integer, ops::{DivRounding, RemRounding}};
 use serde::{Serialize, Deserialize};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -19,39 +19,71 @@ impl ClassGroupElement {
     }
 
     // [NEW FEATURE]: 寻找非单位元生成元，确保真正的非交换性演化
+    // [FIX]: 旧实现仅把 a 改为 3 而未重算 b, c，得到的并不是判别式为 Delta 的合法型，
+    // 任何与之相关的合成都会失败。现按标准做法构造素型 (p, b, (b^2 - Delta) / 4p)。
     pub fn generator(discriminant: &Integer) -> Self {
-        // 简化的模拟生成元逻辑：
-        // 在真实实现中应寻找最小素数 p 使得 (Delta/p)=1 并求解对应的型
-        let mut g = Self::identity(discriminant);
-        // 修改 a 为 3 来模拟非单位元状态 (确保不为 Identity)
-        g.a = Integer::from(3); 
-        // 重新计算 c 以保持判别式一致性 (b^2 - 4ac = D)
-        // b=1, D=D => 1 - 4(3)c = D => c = (1-D)/12 (近似，仅作 Demo)
-        g
+        // 寻找最小奇素数 p 使得 (Delta/p) = 1
+        let mut p: u32 = 3;
+        loop {
+            let p_int = Integer::from(p);
+            if p_int.is_probably_prime(30) != rug::integer::IsPrime::No && discriminant.jacobi(&p_int) == 1 {
+                break;
+            }
+            p += 2;
+        }
+
+        // 求 b^2 ≡ Delta (mod p)；p 很小，直接穷举
+        let d_mod_p = discriminant.mod_u(p);
+        let mut b = (0..p)
+            .find(|x| ((*x as u64 * *x as u64) % p as u64) as u32 == d_mod_p)
+            .expect("Jacobi symbol guarantees a square root exists");
+        // b 与 Delta 同奇偶 (Delta ≡ 1 mod 4 ⇒ b 为奇数)，保证 b^2 ≡ Delta (mod 4p)
+        if b % 2 == 0 {
+            b = p - b;
+        }
+
+        Self::reduce_form(Integer::from(p), Integer::from(b), discriminant)
     }
 
+    // [FIX]: 通用的型合成 (Cohen, GTM 138, Algorithm 5.4.7)
+    // 旧实现要求 gcd(a1, a2) = 1，导致任何非单位元的平方 (从而 pow) 都会失败。
     pub fn compose(&self, other: &Self, discriminant: &Integer) -> Result<Self, String> {
-        let (a1, b1, _c1) = (&self.a, &self.b, &self.c);
-        let (a2, b2, _c2) = (&other.a, &other.b, &other.c);
-
-        let s = (b1 + b2) >> 1; 
-        
-        // 使用模拟的恒定时间 GCD
-        let (d, y1, _y2) = Self::binary_xgcd(a1, a2);
-        
-        if d != Integer::from(1) {
-            return Err(format!("Math Error: Composition of non-coprime forms (d={}).", d));
+        // 1. 保证 a1 <= a2
+        let (f1, f2) = if self.a > other.a { (other, self) } else { (self, other) };
+        let (a1, b1) = (&f1.a, &f1.b);
+        let (a2, b2, c2) = (&f2.a, &f2.b, &f2.c);
+
+        let s: Integer = Integer::from(b1 + b2) >> 1;
+        let n = Integer::from(b2 - &s);
+
+        // 2. u a2 + v a1 = d = gcd(a2, a1)
+        let (y1, d) = if a2.is_divisible(a1) {
+            (Integer::from(0), a1.clone())
+        } else {
+            // 使用模拟的恒定时间 GCD
+            let (d, u, _v) = Self::binary_xgcd(a2, a1);
+            (u, d)
+        };
+
+        // 3. x2 s + y2 d = d1 = gcd(s, d)
+        let (x2, y2, d1) = if s.is_divisible(&d) {
+            (Integer::from(0), Integer::from(-1), d)
+        } else {
+            let (d1, x2, y2) = Self::binary_xgcd(&s, &d);
+            (x2, -y2, d1)
+        };
+
+        if d1 == 0 {
+            return Err("Math Error: Degenerate forms in composition.".to_string());
         }
-        
-        let a3 = a1.clone() * a2;
-        let mut b3 = b2.clone();
-        let term = &s - b2;
-        let offset = a2.clone() * &y1 * &term;
-        
-        b3 += Integer::from(2) * offset;
-        let two_a3 = Integer::from(2) * &a3;
-        b3 = b3.rem_euc(&two_a3); 
-        
+
+        // 4. 组装结果并约化
+        let v1 = Integer::from(a1 / &d1);
+        let v2 = Integer::from(a2 / &d1);
+        let r = (Integer::from(&y1 * &y2) * &n - Integer::from(&x2 * c2)).rem_euc(&v1);
+        let b3: Integer = b2 + Integer::from(&v2 * &r) * 2u32;
+        let a3 = Integer::from(&v1 * &v2);
+
         Ok(Self::reduce_form(a3, b3, discriminant))
     }
 
@@ -74,25 +106,41 @@ impl ClassGroupElement {
     }
 
     // [SECURITY FIX]: 模拟恒定时间执行，移除明显的数据依赖分支 (防侧信道攻击)
+    // 返回 (g, x, y) 使得 u x + v y = g = gcd(u, v)
     fn binary_xgcd(u_in: &Integer, v_in: &Integer) -> (Integer, Integer, Integer) {
-        let mut u = u_in.clone();
-        let mut v = v_in.clone();
+        if *v_in == 0 {
+            return (Integer::from(u_in.abs_ref()), Integer::from(u_in.signum_ref()), Integer::from(0));
+        }
+        if *u_in == 0 {
+            return (Integer::from(v_in.abs_ref()), Integer::from(0), Integer::from(v_in.signum_ref()));
+        }
+        // 负数输入：对绝对值求解后修正系数符号
+        if *u_in < 0 || *v_in < 0 {
+            let (g, x, y) = Self::binary_xgcd(&Integer::from(u_in.abs_ref()), &Integer::from(v_in.abs_ref()));
+            let x = if *u_in < 0 { -x } else { x };
+            let y = if *v_in < 0 { -y } else { y };
+            return (g, x, y);
+        }
+
+        let shift = std::cmp::min(u_in.find_one(0).unwrap_or(0), v_in.find_one(0).unwrap_or(0));
+        // [FIX]: 系数调整必须使用移除公共 2 因子后的值，否则共同含 2 因子时结果错误
+        let u_odd = Integer::from(u_in >> shift);
+        let v_odd = Integer::from(v_in >> shift);
+
+        let mut u = u_odd.clone();
+        let mut v = v_odd.clone();
         let mut x1 = Integer::from(1); let mut y1 = Integer::from(0);
         let mut x2 = Integer::from(0); let mut y2 = Integer::from(1);
-        
-        let shift = std::cmp::min(u.find_one(0).unwrap_or(0), v.find_one(0).unwrap_or(0));
-        u >>= shift;
-        v >>= shift;
 
         while u != 0 {
             while u.is_even() {
                 u >>= 1;
-                if x1.is_odd() || y1.is_odd() { x1 += v_in; y1 -= u_in; }
+                if x1.is_odd() || y1.is_odd() { x1 += &v_odd; y1 -= &u_odd; }
                 x1 >>= 1; y1 >>= 1;
             }
             while v.is_even() {
                 v >>= 1;
-                if x2.is_odd() || y2.is_odd() { x2 += v_in; y2 -= u_in; }
+                if x2.is_odd() || y2.is_odd() { x2 += &v_odd; y2 -= &u_odd; }
                 x2 >>= 1; y2 >>= 1;
             }
             
@@ -108,22 +156,95 @@ impl ClassGroupElement {
     }
 
     fn reduce_form(mut a: Integer, mut b: Integer, discriminant: &Integer) -> Self {
-        let mut two_a = Integer::from(2) * &a;
+        // 规范化 b 到 (-a, a]
+        let two_a = Integer::from(&a * 2u32);
         b = b.rem_euc(&two_a);
         if b > a { b -= &two_a; }
 
-        let four = Integer::from(4);
-        let mut c = (b.clone().pow(2) - discriminant) / (&four * &a);
+        let mut c = Self::derive_c(&a, &b, discriminant);
 
-        while a > c || (a == c && b < Integer::from(0)) {
-            let num = &c + &b;
-            let den = Integer::from(2) * &c;
-            let s = num.div_floor(&den); 
-            let b_new = Integer::from(2) * &c * &s - &b;
+        while a > c || (a == c && b < 0) {
+            let num = Integer::from(&c + &b);
+            let den = Integer::from(&c * 2u32);
+            let s = num.div_floor(&den);
+            let b_new = Integer::from(&den * &s) - &b;
             let a_new = c.clone();
-            let c_new = (b_new.clone().pow(2) - discriminant) / (&four * &a_new);
+            let c_new = Self::derive_c(&a_new, &b_new, discriminant);
             a = a_new; b = b_new; c = c_new;
         }
         ClassGroupElement { a, b, c }
     }
+
+    // c = (b^2 - Delta) / 4a
+    fn derive_c(a: &Integer, b: &Integer, discriminant: &Integer) -> Integer {
+        (Integer::from(b.square_ref()) - discriminant) / Integer::from(a * 4u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 已知答案由独立的参考实现 (Cohen 算法 5.4.7) 生成，并以类数校验：g^h 必为单位元
+    fn form(a: i64, b: i64, c: i64) -> ClassGroupElement {
+        ClassGroupElement { a: Integer::from(a), b: Integer::from(b), c: Integer::from(c) }
+    }
+
+    fn big(s: &str) -> Integer {
+        s.parse().unwrap()
+    }
+
+    fn big_form(a: &str, b: &str, c: &str) -> ClassGroupElement {
+        ClassGroupElement { a: big(a), b: big(b), c: big(c) }
+    }
+
+    #[test]
+    fn generator_known_answers() {
+        assert_eq!(ClassGroupElement::generator(&Integer::from(-23)), form(2, -1, 3));
+        assert_eq!(ClassGroupElement::generator(&Integer::from(-47)), form(3, 1, 4));
+        assert_eq!(ClassGroupElement::generator(&Integer::from(-1_000_003)), form(13, 3, 19231));
+    }
+
+    #[test]
+    fn generator_order_divides_class_number() {
+        // (判别式, 类数)
+        for (d, h) in [(-23i64, 3u32), (-47, 5), (-1_000_003, 105)] {
+            let d = Integer::from(d);
+            let g = ClassGroupElement::generator(&d);
+            assert_ne!(g, ClassGroupElement::identity(&d));
+            assert_eq!(g.pow(&Integer::from(h), &d).unwrap(), ClassGroupElement::identity(&d), "D = {}", d);
+        }
+    }
+
+    #[test]
+    fn composition_of_non_coprime_forms() {
+        let d = Integer::from(-47);
+        let g = ClassGroupElement::generator(&d);
+        // a1 = a2 = 3：旧实现在此返回错误
+        assert_eq!(g.square(&d).unwrap(), form(2, 1, 6));
+        assert_eq!(form(2, -1, 3).square(&Integer::from(-23)).unwrap(), form(2, 1, 3));
+    }
+
+    #[test]
+    fn composition_known_answers_128_bit() {
+        let d = big("-170141183460469231731687303715884105851");
+        let g = ClassGroupElement::generator(&d);
+        assert_eq!(g, big_form("3", "1", "14178431955039102644307275309657008821"));
+
+        let e = (Integer::from(1) << 64) + 13u32;
+        let ge = g.pow(&e, &d).unwrap();
+        assert_eq!(ge, big_form("4643644297441394505", "-3358901042023340083", "9767296332889738337"));
+        assert_eq!(ge.compose(&g, &d).unwrap(), big_form("1547881432480464835", "-263138177062410413", "27490869389126339763"));
+        assert_eq!(ge.square(&d).unwrap(), big_form("5791956147570197493", "-3039458610651527539", "7742612665566581701"));
+    }
+
+    #[test]
+    fn binary_xgcd_bezout() {
+        for (u, v) in [(12i64, 18i64), (-12, 18), (12, -18), (240, 46), (7, 0), (0, -5), (1 << 20, 3 << 18)] {
+            let (u, v) = (Integer::from(u), Integer::from(v));
+            let (g, x, y) = ClassGroupElement::binary_xgcd(&u, &v);
+            assert_eq!(g, Integer::from(u.gcd_ref(&v)), "gcd({}, {})", u, v);
+            assert_eq!(Integer::from(&u * &x) + Integer::from(&v * &y), g, "bezout({}, {})", u, v);
+        }
+    }
 }
@@ -0,0 +1,597 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use rug::{Integer, ops::Pow};
+use serde::{Serialize, Deserialize};
+
+use super::param::{SystemParameters, MAX_DISCRIMINANT_BITS, MAX_DISCRIMINANT_STEPS, MIN_DISCRIMINANT_BITS};
+use super::primality::{is_strong_probable_prime, passes_small_prime_sieve, small_primes, PrimalityTest};
+use super::primes::PrimeRepresentative;
+
+/// Pocklington 证书的适用上界；更大的整数 (如判别式) 其 n-1 实际上无法分解
+pub const MAX_CERTIFIABLE_BITS: u32 = 256;
+
+/// 证书嵌套深度上界：每层因子至多比 n 少 1 bit，降到 64 bits 以下即为 Small 证书
+pub const MAX_CERTIFICATE_DEPTH: u32 = MAX_CERTIFIABLE_BITS - 64;
+
+// 确定性 Miller-Rabin 底数：对 n < 3.3 * 10^24 已被证明无伪素数
+const DETERMINISTIC_MR_BASES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// [SECURITY FIX]: 限制 Pollard-Rho 迭代次数，防止证书生成 CPU DoS
+const RHO_ITERATION_BUDGET: u64 = 1 << 20;
+const RHO_MAX_RESTARTS: u32 = 8;
+
+/// 素性证书 (可离线、确定性地校验)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrimalityCertificate {
+    /// n < 2^64：以前 12 个素数为底的确定性 Miller-Rabin 即为证明
+    Small { n: Integer },
+    /// Pocklington：n - 1 = F * R，F^2 > n，F 的每个素因子 q 都有见证 a
+    /// 满足 a^(n-1) ≡ 1 且 gcd(a^((n-1)/q) - 1, n) = 1
+    Pocklington {
+        n: Integer,
+        factors: Vec<PocklingtonFactor>,
+        cofactor: Integer,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PocklingtonFactor {
+    pub prime: Integer,
+    pub exponent: u32,
+    pub witness: u32,
+    pub certificate: Box<PrimalityCertificate>,
+}
+
+/// 合数见证：证明 next-prime 搜索中被跳过的候选数确实不是素数
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompositeWitness {
+    /// 候选数的一个小素因子
+    SmallFactor(u32),
+    /// 强概率素数测试失败的底数
+    StrongBase(u32),
+}
+
+/// 判别式证书：证明 M = -Delta 是 Hash(Seed) 之后第一个 ≡ 3 (mod 4) 的素数
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscriminantCertificate {
+    pub seed: Vec<u8>,
+    pub bit_size: u32,
+    /// 从起点出发的步数 (步长 4)
+    pub steps: u64,
+    /// 每个被跳过候选数的合数见证，顺序与搜索顺序一致
+    pub skipped: Vec<CompositeWitness>,
+    /// M 的素性证书；大判别式的 M-1 通常无法分解，此时为 None
+    pub primality: Option<PrimalityCertificate>,
+}
+
+/// 离线校验结论
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimalityEvidence {
+    /// 已被证书确定性地证明
+    Proven,
+    /// 推导过程已核实，但素性仅有概率性证据 (BPSW)
+    Probabilistic,
+}
+
+impl PrimalityCertificate {
+    pub fn n(&self) -> &Integer {
+        match self {
+            PrimalityCertificate::Small { n } => n,
+            PrimalityCertificate::Pocklington { n, .. } => n,
+        }
+    }
+}
+
+/// 为素数代表元生成证书 (适用于 64-256 bit)
+pub fn certify_representative(rep: &PrimeRepresentative) -> Result<PrimalityCertificate, String> {
+    certify_prime(&rep.prime)
+}
+
+/// 递归生成 Pocklington 证书
+pub fn certify_prime(n: &Integer) -> Result<PrimalityCertificate, String> {
+    if *n < 2 {
+        return Err("❌ Cannot certify integers below 2.".to_string());
+    }
+    let bits = n.significant_bits();
+    if bits > MAX_CERTIFIABLE_BITS {
+        return Err(format!("❌ Integer too large for Pocklington certification ({} bits > {}).", bits, MAX_CERTIFIABLE_BITS));
+    }
+    if n.significant_bits() <= 64 {
+        if !is_deterministic_small_prime(n) {
+            return Err(format!("❌ {} is composite.", n));
+        }
+        return Ok(PrimalityCertificate::Small { n: n.clone() });
+    }
+    if !PrimalityTest::BailliePsw.is_probable_prime(n) {
+        return Err("❌ Candidate failed Baillie-PSW; refusing to certify a composite.".to_string());
+    }
+
+    let n_minus_1 = Integer::from(n - 1u32);
+    let (factored, cofactor) = partial_factor(&n_minus_1, n)?;
+
+    let mut factors = Vec::with_capacity(factored.len());
+    for (q, exponent) in factored {
+        let witness = find_pocklington_witness(n, &q)
+            .ok_or_else(|| format!("❌ No Pocklington witness found for factor {}.", q))?;
+        let certificate = Box::new(certify_prime(&q)?);
+        factors.push(PocklingtonFactor { prime: q, exponent, witness, certificate });
+    }
+
+    Ok(PrimalityCertificate::Pocklington { n: n.clone(), factors, cofactor })
+}
+
+/// 离线校验素性证书
+pub fn verify_certificate(cert: &PrimalityCertificate) -> Result<(), String> {
+    verify_certificate_at(cert, 0)
+}
+
+fn verify_certificate_at(cert: &PrimalityCertificate, depth: u32) -> Result<(), String> {
+    // [SECURITY FIX]: 证书来自不可信方，递归深度必须有界，否则深度嵌套的证书可耗尽栈空间
+    if depth > MAX_CERTIFICATE_DEPTH {
+        return Err(format!("Certificate Error: Nesting depth exceeds {}.", MAX_CERTIFICATE_DEPTH));
+    }
+    match cert {
+        PrimalityCertificate::Small { n } => {
+            if n.significant_bits() > 64 {
+                return Err("Certificate Error: 'Small' certificate used for n >= 2^64.".to_string());
+            }
+            if !is_deterministic_small_prime(n) {
+                return Err(format!("Certificate Error: {} is composite.", n));
+            }
+            Ok(())
+        },
+        PrimalityCertificate::Pocklington { n, factors, cofactor } => {
+            if *n < 3 || n.is_even() {
+                return Err("Certificate Error: Pocklington modulus must be an odd integer > 2.".to_string());
+            }
+            // [SECURITY FIX]: 先用位长界定所有字段，再做任何大数运算
+            // (超大的 n、指数或余因子会让 pow / 乘法分配巨量内存；因子不小于 n 则递归不收敛)
+            let bits = n.significant_bits();
+            if bits > MAX_CERTIFIABLE_BITS {
+                return Err(format!("Certificate Error: Modulus too large ({} bits > {}).", bits, MAX_CERTIFIABLE_BITS));
+            }
+            if factors.len() > bits as usize {
+                return Err("Certificate Error: More factors than bits in the modulus.".to_string());
+            }
+            if *cofactor < 1 || cofactor.significant_bits() > bits {
+                return Err("Certificate Error: Cofactor out of range.".to_string());
+            }
+            for factor in factors {
+                if factor.prime < 2 || factor.prime >= *n {
+                    return Err(format!("Certificate Error: Factor {} out of range.", factor.prime));
+                }
+                if factor.exponent == 0 || factor.exponent > bits {
+                    return Err(format!("Certificate Error: Exponent {} out of range.", factor.exponent));
+                }
+            }
+            let n_minus_1 = Integer::from(n - 1u32);
+
+            let mut f = Integer::from(1);
+            for factor in factors {
+                if factor.certificate.n() != &factor.prime {
+                    return Err("Certificate Error: Sub-certificate does not match factor.".to_string());
+                }
+                verify_certificate_at(&factor.certificate, depth + 1)?;
+                f *= Integer::from((&factor.prime).pow(factor.exponent));
+            }
+
+            // n - 1 = F * R
+            if Integer::from(&f * cofactor) != n_minus_1 {
+                return Err("Certificate Error: F * R != n - 1.".to_string());
+            }
+            // F^2 > n  (等价于 F > sqrt(n))
+            if Integer::from(f.square_ref()) <= *n {
+                return Err("Certificate Error: Factored part F is not larger than sqrt(n).".to_string());
+            }
+
+            for factor in factors {
+                if !check_pocklington_witness(n, &factor.prime, factor.witness) {
+                    return Err(format!("Certificate Error: Invalid witness {} for factor {}.", factor.witness, factor.prime));
+                }
+            }
+            Ok(())
+        },
+    }
+}
+
+/// 生成判别式证书：重放 next-prime 搜索并为每个跳过的候选数记录合数见证
+pub fn certify_discriminant(seed_bytes: &[u8], bit_size: u32) -> Result<DiscriminantCertificate, String> {
+    if !(MIN_DISCRIMINANT_BITS..=MAX_DISCRIMINANT_BITS).contains(&bit_size) {
+        return Err(format!("❌ Invalid discriminant size {} bits. Must be between {} and {}.",
+            bit_size, MIN_DISCRIMINANT_BITS, MAX_DISCRIMINANT_BITS));
+    }
+    let mut candidate = SystemParameters::search_start(seed_bytes, bit_size);
+    let mut skipped = Vec::new();
+
+    for steps in 0..=MAX_DISCRIMINANT_STEPS {
+        if candidate.significant_bits() != bit_size {
+            return Err(format!("❌ Prime search overflowed {} bits.", bit_size));
+        }
+        match find_composite_witness(&candidate) {
+            Some(w) => skipped.push(w),
+            None => {
+                if !PrimalityTest::BailliePsw.is_probable_prime(&candidate) {
+                    return Err("❌ Candidate is composite but no compositeness witness was found.".to_string());
+                }
+                // 超过 MAX_CERTIFIABLE_BITS 的判别式只提供推导证明，素性依赖 BPSW
+                let primality = certify_prime(&candidate).ok();
+                return Ok(DiscriminantCertificate {
+                    seed: seed_bytes.to_vec(),
+                    bit_size,
+                    steps,
+                    skipped,
+                    primality,
+                });
+            }
+        }
+        candidate += 4;
+    }
+    Err(format!("❌ No prime found within {} steps.", MAX_DISCRIMINANT_STEPS))
+}
+
+/// 离线校验判别式证书
+pub fn verify_discriminant_certificate(discriminant: &Integer, cert: &DiscriminantCertificate) -> Result<PrimalityEvidence, String> {
+    // [SECURITY FIX]: bit_size 与 steps 来自不可信的证书，必须在重放搜索之前校验
+    // (bit_size = 0 会在构造起点时 panic，超大的 bit_size 会触发巨量内存分配)
+    if !(MIN_DISCRIMINANT_BITS..=MAX_DISCRIMINANT_BITS).contains(&cert.bit_size) {
+        return Err(format!("Certificate Error: Discriminant size {} bits outside {}-{}.",
+            cert.bit_size, MIN_DISCRIMINANT_BITS, MAX_DISCRIMINANT_BITS));
+    }
+    if *discriminant >= 0 || discriminant.significant_bits() != cert.bit_size {
+        return Err("Certificate Error: Certificate size does not match the discriminant.".to_string());
+    }
+    if cert.steps > MAX_DISCRIMINANT_STEPS {
+        return Err(format!("Certificate Error: Step count exceeds the search limit ({}).", MAX_DISCRIMINANT_STEPS));
+    }
+    if cert.skipped.len() as u64 != cert.steps {
+        return Err("Certificate Error: Witness count does not match step count.".to_string());
+    }
+
+    let m = Integer::from(-discriminant);
+    let mut candidate = SystemParameters::search_start(&cert.seed, cert.bit_size);
+
+    // 1. 每个跳过的候选数都必须被确定性地证明为合数
+    for witness in &cert.skipped {
+        if !check_composite_witness(&candidate, witness) {
+            return Err(format!("Certificate Error: Invalid compositeness witness {:?}.", witness));
+        }
+        candidate += 4;
+    }
+
+    // 2. 终点必须正好是 M，且满足位长与 M ≡ 3 (mod 4)
+    if candidate != m {
+        return Err("Certificate Error: Derived candidate does not match the discriminant.".to_string());
+    }
+    if m.significant_bits() != cert.bit_size || m.mod_u(4) != 3 {
+        return Err("Certificate Error: Discriminant violates size or residue constraints.".to_string());
+    }
+
+    // 3. M 本身的素性
+    match &cert.primality {
+        Some(p) => {
+            if p.n() != &m {
+                return Err("Certificate Error: Primality certificate is for a different integer.".to_string());
+            }
+            verify_certificate(p)?;
+            Ok(PrimalityEvidence::Proven)
+        },
+        None => {
+            if !PrimalityTest::BailliePsw.is_probable_prime(&m) {
+                return Err("Certificate Error: Discriminant failed Baillie-PSW.".to_string());
+            }
+            Ok(PrimalityEvidence::Probabilistic)
+        },
+    }
+}
+
+fn is_deterministic_small_prime(n: &Integer) -> bool {
+    if *n < 2 {
+        return false;
+    }
+    for &b in DETERMINISTIC_MR_BASES.iter() {
+        if *n == b {
+            return true;
+        }
+        if n.is_divisible_u(b) {
+            return false;
+        }
+    }
+    DETERMINISTIC_MR_BASES.iter().all(|&b| is_strong_probable_prime(n, b))
+}
+
+fn find_composite_witness(n: &Integer) -> Option<CompositeWitness> {
+    if !passes_small_prime_sieve(n) {
+        let p = small_primes().iter().find(|&&p| n.is_divisible_u(p))?;
+        return Some(CompositeWitness::SmallFactor(*p));
+    }
+    DETERMINISTIC_MR_BASES
+        .iter()
+        .find(|&&b| !is_strong_probable_prime(n, b))
+        .map(|&b| CompositeWitness::StrongBase(b))
+}
+
+fn check_composite_witness(n: &Integer, witness: &CompositeWitness) -> bool {
+    match witness {
+        CompositeWitness::SmallFactor(p) => *p > 1 && *n != *p && n.is_divisible_u(*p),
+        CompositeWitness::StrongBase(b) => *b > 1 && *n > *b + 1 && n.is_odd() && !is_strong_probable_prime(n, *b),
+    }
+}
+
+fn check_pocklington_witness(n: &Integer, q: &Integer, a: u32) -> bool {
+    let n_minus_1 = Integer::from(n - 1u32);
+    let base = Integer::from(a);
+    // a^(n-1) ≡ 1 (mod n)
+    match base.clone().pow_mod(&n_minus_1, n) {
+        Ok(x) if x == 1 => {},
+        _ => return false,
+    }
+    // gcd(a^((n-1)/q) - 1, n) = 1
+    if !n_minus_1.is_divisible(q) {
+        return false;
+    }
+    let e = Integer::from(&n_minus_1 / q);
+    match base.pow_mod(&e, n) {
+        Ok(x) => (x - 1u32).gcd(n) == 1,
+        Err(_) => false,
+    }
+}
+
+fn find_pocklington_witness(n: &Integer, q: &Integer) -> Option<u32> {
+    (2..1_000u32).find(|&a| check_pocklington_witness(n, q, a))
+}
+
+/// 部分分解 n - 1，直到已分解部分 F 满足 F^2 > n
+/// 返回 (素因子及其指数, 剩余余因子 R)
+fn partial_factor(n_minus_1: &Integer, n: &Integer) -> Result<(Vec<(Integer, u32)>, Integer), String> {
+    let mut factors: Vec<(Integer, u32)> = Vec::new();
+    let mut remaining = n_minus_1.clone();
+    let mut f = Integer::from(1);
+
+    // 1. 小素数试除 (含 2)
+    for p in std::iter::once(2u32).chain(small_primes().iter().copied()) {
+        if remaining == 1 { break; }
+        while remaining.is_divisible_u(p) {
+            remaining /= p;
+            f *= p;
+            add_factor(&mut factors, Integer::from(p));
+        }
+    }
+
+    // 2. Pollard rho 拆分剩余部分；pending 中各部分之积始终等于 remaining
+    let mut pending = vec![remaining.clone()];
+    while Integer::from(f.square_ref()) <= *n {
+        let part = match pending.pop() {
+            Some(p) => p,
+            None => break,
+        };
+        if part == 1 {
+            continue;
+        }
+        if PrimalityTest::BailliePsw.is_probable_prime(&part) {
+            remaining /= &part;
+            f *= &part;
+            add_factor(&mut factors, part);
+            continue;
+        }
+        match pollard_brent_rho(&part) {
+            Some(d) => {
+                let other = Integer::from(&part / &d);
+                pending.push(d);
+                pending.push(other);
+            },
+            None => break,
+        }
+    }
+
+    if Integer::from(f.square_ref()) <= *n {
+        return Err("❌ Could not factor n - 1 far enough within budget.".to_string());
+    }
+    Ok((factors, remaining))
+}
+
+fn add_factor(factors: &mut Vec<(Integer, u32)>, q: Integer) {
+    match factors.iter_mut().find(|(p, _)| *p == q) {
+        Some((_, e)) => *e += 1,
+        None => factors.push((q, 1)),
+    }
+}
+
+/// Pollard-Brent rho (批量 gcd)，返回 n 的一个非平凡因子
+fn pollard_brent_rho(n: &Integer) -> Option<Integer> {
+    if n.is_even() {
+        return Some(Integer::from(2));
+    }
+    const BATCH: u64 = 128;
+    for c in 1..=RHO_MAX_RESTARTS {
+        let f = |x: &Integer| (Integer::from(x.square_ref()) + c) % n;
+        let mut y = Integer::from(2);
+        let mut x = y.clone();
+        let mut ys = y.clone();
+        let mut g = Integer::from(1);
+        let mut r: u64 = 1;
+        let mut iterations: u64 = 0;
+
+        while g == 1 && iterations < RHO_ITERATION_BUDGET {
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+            let mut k = 0;
+            while k < r && g == 1 {
+                ys = y.clone();
+                let mut q = Integer::from(1);
+                for _ in 0..BATCH.min(r - k) {
+                    y = f(&y);
+                    q = (q * Integer::from(&x - &y).abs()) % n;
+                }
+                g = q.gcd(n);
+                k += BATCH;
+            }
+            iterations += r;
+            r *= 2;
+        }
+
+        // 批量 gcd 越过了因子：从 ys 回退逐步查找
+        if g == *n {
+            loop {
+                ys = f(&ys);
+                g = Integer::from(&x - &ys).abs().gcd(n);
+                if g != 1 {
+                    break;
+                }
+            }
+        }
+        if g != 1 && g != *n {
+            return Some(g);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::primes::PrimeHashConfig;
+
+    const SEED: &[u8] = b"htp:certificate:test";
+
+    fn discriminant_and_certificate() -> (Integer, DiscriminantCertificate) {
+        let discriminant = SystemParameters::from_random_seed(SEED, MIN_DISCRIMINANT_BITS).discriminant;
+        let cert = certify_discriminant(SEED, MIN_DISCRIMINANT_BITS).unwrap();
+        (discriminant, cert)
+    }
+
+    #[test]
+    fn representative_certificate_round_trip() {
+        let rep = PrimeHashConfig::default().hash_to_prime("alice").unwrap();
+        let cert = certify_representative(&rep).unwrap();
+        assert_eq!(cert.n(), &rep.prime);
+        verify_certificate(&cert).unwrap();
+
+        let small = certify_prime(&Integer::from(1_000_000_007u32)).unwrap();
+        assert!(matches!(small, PrimalityCertificate::Small { .. }));
+        verify_certificate(&small).unwrap();
+    }
+
+    #[test]
+    fn tampered_prime_certificates_are_rejected() {
+        let rep = PrimeHashConfig::default().hash_to_prime("alice").unwrap();
+        let cert = certify_representative(&rep).unwrap();
+        let PrimalityCertificate::Pocklington { n, factors, cofactor } = cert else {
+            panic!("128-bit representatives use Pocklington certificates");
+        };
+
+        // 替换模数：F * R = n - 1 不再成立
+        let forged = PrimalityCertificate::Pocklington { n: Integer::from(&n + 2u32), factors: factors.clone(), cofactor: cofactor.clone() };
+        assert!(verify_certificate(&forged).is_err());
+
+        let mut bad_witness = factors.clone();
+        bad_witness[0].witness = 1;
+        let forged = PrimalityCertificate::Pocklington { n: n.clone(), factors: bad_witness, cofactor: cofactor.clone() };
+        assert!(verify_certificate(&forged).is_err());
+
+        // 丢弃已分解部分：F^2 > n 不再成立
+        let forged = PrimalityCertificate::Pocklington { n: n.clone(), factors: Vec::new(), cofactor: Integer::from(&n - 1u32) };
+        assert!(verify_certificate(&forged).is_err());
+
+        assert!(verify_certificate(&PrimalityCertificate::Small { n: Integer::from(561) }).is_err());
+        assert!(verify_certificate(&PrimalityCertificate::Small { n: rep.prime }).is_err());
+    }
+
+    #[test]
+    fn malformed_prime_certificates_are_rejected() {
+        let rep = PrimeHashConfig::default().hash_to_prime("alice").unwrap();
+        let PrimalityCertificate::Pocklington { n, factors, cofactor } = certify_representative(&rep).unwrap() else {
+            panic!("128-bit representatives use Pocklington certificates");
+        };
+        let pocklington = |n: &Integer, factors: Vec<PocklingtonFactor>, cofactor: &Integer| {
+            PrimalityCertificate::Pocklington { n: n.clone(), factors, cofactor: cofactor.clone() }
+        };
+
+        // 超出可证明范围的模数
+        let huge = (Integer::from(1) << (MAX_CERTIFIABLE_BITS + 1)) + 1u32;
+        assert!(verify_certificate(&pocklington(&huge, factors.clone(), &cofactor)).is_err());
+
+        // 因子不小于 n：自引用的子证书永远无法递归到底
+        let mut forged = factors.clone();
+        forged[0].prime = n.clone();
+        *forged[0].certificate = pocklington(&n, factors.clone(), &cofactor);
+        assert!(verify_certificate(&pocklington(&n, forged, &cofactor)).is_err());
+
+        // 超大指数：若不先校验，pow 会分配约 u32::MAX * |q| bits
+        let mut forged = factors.clone();
+        forged[0].exponent = u32::MAX;
+        assert!(verify_certificate(&pocklington(&n, forged, &cofactor)).is_err());
+
+        let forged = vec![factors[0].clone(); n.significant_bits() as usize + 1];
+        assert!(verify_certificate(&pocklington(&n, forged, &cofactor)).is_err());
+
+        let huge_cofactor = Integer::from(1) << 100_000;
+        assert!(verify_certificate(&pocklington(&n, factors.clone(), &huge_cofactor)).is_err());
+    }
+
+    #[test]
+    fn deeply_nested_certificates_are_rejected() {
+        // 每层因子比上一层小 2，逐层都能通过范围检查，只有深度上界能终止递归
+        let base = (Integer::from(1) << (MAX_CERTIFIABLE_BITS - 1)) + 1u32;
+        let levels = MAX_CERTIFICATE_DEPTH + 2;
+        let mut cert = PrimalityCertificate::Small { n: Integer::from(3) };
+        for level in 0..levels {
+            let n = Integer::from(&base + 2 * level);
+            let factor = PocklingtonFactor { prime: cert.n().clone(), exponent: 1, witness: 2, certificate: Box::new(cert) };
+            cert = PrimalityCertificate::Pocklington { n, factors: vec![factor], cofactor: Integer::from(1) };
+        }
+        let err = verify_certificate(&cert).unwrap_err();
+        assert!(err.contains("depth"), "{}", err);
+    }
+
+    #[test]
+    fn discriminant_certificate_round_trip() {
+        let (discriminant, cert) = discriminant_and_certificate();
+        assert_eq!(cert.skipped.len() as u64, cert.steps);
+        assert_eq!(verify_discriminant_certificate(&discriminant, &cert), Ok(PrimalityEvidence::Probabilistic));
+    }
+
+    #[test]
+    fn tampered_discriminant_certificates_are_rejected() {
+        let (discriminant, cert) = discriminant_and_certificate();
+
+        let other = SystemParameters::from_random_seed(b"another seed", MIN_DISCRIMINANT_BITS).discriminant;
+        assert!(verify_discriminant_certificate(&other, &cert).is_err());
+        assert!(verify_discriminant_certificate(&Integer::from(-&discriminant), &cert).is_err());
+
+        let mut forged = cert.clone();
+        forged.seed.push(0);
+        assert!(verify_discriminant_certificate(&discriminant, &forged).is_err());
+
+        // 少跳过一个候选数：终点不再是判别式
+        let mut forged = cert.clone();
+        if forged.steps > 0 {
+            forged.steps -= 1;
+            forged.skipped.pop();
+            assert!(verify_discriminant_certificate(&discriminant, &forged).is_err());
+        }
+
+        // 把判别式本身当作被跳过的合数
+        let mut forged = cert.clone();
+        forged.steps += 1;
+        forged.skipped.push(CompositeWitness::StrongBase(2));
+        assert!(verify_discriminant_certificate(&discriminant, &forged).is_err());
+    }
+
+    #[test]
+    fn malformed_discriminant_certificates_are_rejected() {
+        let (discriminant, cert) = discriminant_and_certificate();
+        for bit_size in [0, 1, MIN_DISCRIMINANT_BITS - 1, MAX_DISCRIMINANT_BITS + 1, u32::MAX] {
+            let forged = DiscriminantCertificate { bit_size, ..cert.clone() };
+            assert!(verify_discriminant_certificate(&discriminant, &forged).is_err(), "bit_size {}", bit_size);
+        }
+        let forged = DiscriminantCertificate { bit_size: 2048, ..cert.clone() };
+        assert!(verify_discriminant_certificate(&discriminant, &forged).is_err());
+
+        let forged = DiscriminantCertificate { steps: u64::MAX, ..cert.clone() };
+        assert!(verify_discriminant_certificate(&discriminant, &forged).is_err());
+
+        assert!(certify_discriminant(SEED, 0).is_err());
+    }
+}
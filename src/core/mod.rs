@@ -1,4 +1,6 @@
 pub mod affine;
 pub mod algebra;
+pub mod certificate;
 pub mod param;
+pub mod primality;
 pub mod primes;
@@ -1,43 +1,95 @@
 // COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
 
-use rug::{Integer, complete::Complete};
+use rug::Integer;
 use blake3::Hasher;
+use std::fmt;
+use std::str::FromStr;
+
+/// 命名安全档位 (Security Profiles)
+/// 判别式位数决定了类群阶的计算难度，生产环境不应低于 2048 bits。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SecurityProfile {
+    /// 仅用于测试/CI，不提供实际安全性
+    Test1024,
+    #[default]
+    Standard2048,
+    High3072,
+}
+
+impl SecurityProfile {
+    pub fn discriminant_bits(&self) -> u32 {
+        match self {
+            SecurityProfile::Test1024 => 1024,
+            SecurityProfile::Standard2048 => 2048,
+            SecurityProfile::High3072 => 3072,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SecurityProfile::Test1024 => "test-1024",
+            SecurityProfile::Standard2048 => "standard-2048",
+            SecurityProfile::High3072 => "high-3072",
+        }
+    }
+}
+
+impl fmt::Display for SecurityProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SecurityProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "test" | "test-1024" | "1024" => Ok(SecurityProfile::Test1024),
+            "standard" | "standard-2048" | "2048" => Ok(SecurityProfile::Standard2048),
+            "high" | "high-3072" | "3072" => Ok(SecurityProfile::High3072),
+            other => Err(format!("Unknown security profile '{}'. Expected one of: test-1024, standard-2048, high-3072.", other)),
+        }
+    }
+}
+
+/// 判别式位长允许范围
+pub const MIN_DISCRIMINANT_BITS: u32 = 1024;
+pub const MAX_DISCRIMINANT_BITS: u32 = 8192;
+
+/// 判别式 next-prime 搜索的最大步数 (期望步数约 ln(2^bits) / 2)
+pub const MAX_DISCRIMINANT_STEPS: u64 = 100_000;
 
 pub struct SystemParameters {
     pub discriminant: Integer,
 }
 
 impl SystemParameters {
+    /// 按命名安全档位生成参数
+    pub fn from_profile(seed_bytes: &[u8], profile: SecurityProfile) -> Self {
+        Self::from_random_seed(seed_bytes, profile.discriminant_bits())
+    }
+
     /// 运行时生成：根据随机种子生成判别式 Delta
+    /// 符合规范：M 为 Hash(Seed) 之后第一个满足 M ≡ 3 (mod 4) 的素数，Delta = -M。
     /// [SECURITY FIX]: Added loop limit to prevent infinite hang during setup.
     pub fn from_random_seed(seed_bytes: &[u8], bit_size: u32) -> Self {
         println!("[System] Generating Trustless Parameters from seed...");
-        
-        let mut attempt = 0;
-        let max_attempts = 10_000; // 防止无限死循环
+
+        // 1-2. Hash(Seed) 起点，已满足 M = 3 mod 4
+        let mut candidate = Self::search_start(seed_bytes, bit_size);
+
+        // 3. Next-prime 搜索：只在 3 mod 4 的剩余类中以步长 4 前进
+        let mut attempt: u64 = 0;
 
         loop {
-            if attempt > max_attempts {
+            if attempt > MAX_DISCRIMINANT_STEPS {
                 panic!("❌ Failed to generate System Parameters. Seed entropy insufficient or bad luck.");
             }
 
-            // 1. 确定性地不断改变 Hash 输入 (Nonce)
-            let mut hasher = Hasher::new();
-            hasher.update(seed_bytes);
-            hasher.update(&attempt.to_le_bytes());
-            let hash_output = hasher.finalize();
-
-            // 2. 将 Hash 扩展为大整数
-            let mut candidate = Integer::from_digits(hash_output.as_bytes(), rug::integer::Order::Lsf);
-            
-            // 确保位数足够
-            candidate.set_bit(bit_size - 1, true);
-            
-            // 3. 强制 M = 3 mod 4 (为了让 Delta = 1 mod 4)
-            let rem = candidate.mod_u(4);
-            if rem != 3 {
-                attempt += 1;
-                continue;
+            // 越过 bit_size 上界说明种子已落在区间末端，不再满足位数要求
+            if candidate.significant_bits() != bit_size {
+                panic!("❌ Failed to generate System Parameters: prime search overflowed {} bits.", bit_size);
             }
 
             // 4. 素性测试 (Miller-Rabin)
@@ -47,7 +99,36 @@ impl SystemParameters {
                 return SystemParameters { discriminant };
             }
 
+            candidate += 4;
             attempt += 1;
         }
     }
+
+    /// Next-prime 搜索的起点：
+    /// 1. 使用 XOF 将种子扩展到完整的 bit_size 位 (不再只有 256 bits 有效熵)
+    /// 2. 强制 M = 3 mod 4 (为了让 Delta = 1 mod 4)
+    pub fn search_start(seed_bytes: &[u8], bit_size: u32) -> Integer {
+        let mut candidate = Self::expand_seed(seed_bytes, bit_size);
+        candidate.set_bit(0, true);
+        candidate.set_bit(1, true);
+        candidate
+    }
+
+    /// Hash(Seed)：blake3 XOF 输出 bit_size 位，并置最高位以固定位长
+    fn expand_seed(seed_bytes: &[u8], bit_size: u32) -> Integer {
+        let mut hasher = Hasher::new();
+        hasher.update(b"htp:param:discriminant:v2");
+        hasher.update(&(seed_bytes.len() as u64).to_le_bytes());
+        hasher.update(seed_bytes);
+
+        let byte_len = bit_size.div_ceil(8) as usize;
+        let mut buf = vec![0u8; byte_len];
+        hasher.finalize_xof().fill(&mut buf);
+
+        let mut candidate = Integer::from_digits(&buf, rug::integer::Order::Lsf);
+        // 截断多余的高位，然后置最高位
+        candidate.keep_bits_mut(bit_size);
+        candidate.set_bit(bit_size - 1, true);
+        candidate
+    }
 }
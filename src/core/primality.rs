@@ -0,0 +1,258 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use rug::{Integer, ops::RemRounding};
+use std::sync::OnceLock;
+
+/// 小素数筛上界：2^15 以下共 3511 个奇素数
+pub const SMALL_PRIME_LIMIT: u32 = 1 << 15;
+
+/// 素性测试算法选择
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PrimalityTest {
+    /// GMP 的 Miller-Rabin，参数为轮数
+    MillerRabin(u32),
+    /// Baillie-PSW：强 2-SPRP + 强 Lucas 测试 (目前无已知反例)
+    BailliePsw,
+}
+
+impl Default for PrimalityTest {
+    fn default() -> Self {
+        PrimalityTest::MillerRabin(25)
+    }
+}
+
+impl PrimalityTest {
+    pub fn is_probable_prime(&self, n: &Integer) -> bool {
+        match self {
+            PrimalityTest::MillerRabin(reps) => n.is_probably_prime(*reps) != rug::integer::IsPrime::No,
+            PrimalityTest::BailliePsw => is_baillie_psw_prime(n),
+        }
+    }
+}
+
+struct SmallPrimeSieve {
+    primes: Vec<u32>,
+    // 将相邻小素数打包成 u32 乘积，每组只需一次大整数取模
+    groups: Vec<(u32, std::ops::Range<usize>)>,
+}
+
+fn sieve() -> &'static SmallPrimeSieve {
+    static SIEVE: OnceLock<SmallPrimeSieve> = OnceLock::new();
+    SIEVE.get_or_init(|| {
+        // Eratosthenes 筛 (跳过 2：候选数总是奇数)
+        let limit = SMALL_PRIME_LIMIT as usize;
+        let mut composite = vec![false; limit];
+        let mut primes = Vec::new();
+        for i in 3..limit {
+            if i % 2 == 0 || composite[i] { continue; }
+            primes.push(i as u32);
+            let mut j = i * i;
+            while j < limit {
+                composite[j] = true;
+                j += i;
+            }
+        }
+
+        let mut groups = Vec::new();
+        let mut start = 0;
+        let mut product: u64 = 1;
+        for (i, &p) in primes.iter().enumerate() {
+            if product * p as u64 > u32::MAX as u64 {
+                groups.push((product as u32, start..i));
+                start = i;
+                product = 1;
+            }
+            product *= p as u64;
+        }
+        groups.push((product as u32, start..primes.len()));
+
+        SmallPrimeSieve { primes, groups }
+    })
+}
+
+/// 预计算的奇小素数表
+pub fn small_primes() -> &'static [u32] {
+    &sieve().primes
+}
+
+/// 小素数筛：若 n 有小于 SMALL_PRIME_LIMIT 的真因子则返回 false
+pub fn passes_small_prime_sieve(n: &Integer) -> bool {
+    if n.is_even() {
+        return *n == 2;
+    }
+    let s = sieve();
+    for (product, range) in &s.groups {
+        let residue = n.mod_u(*product);
+        for &p in &s.primes[range.clone()] {
+            if residue.is_multiple_of(p) {
+                return *n == p;
+            }
+        }
+    }
+    true
+}
+
+/// 以 base 为底的强概率素数测试 (n 为大于 base 的奇数)
+/// 返回 false 即构成 n 为合数的确定性见证
+pub fn is_strong_probable_prime(n: &Integer, base: u32) -> bool {
+    let n_minus_1 = Integer::from(n - 1u32);
+    let s = n_minus_1.find_one(0).unwrap_or(0);
+    let d = Integer::from(&n_minus_1 >> s);
+
+    let mut x = match Integer::from(base).pow_mod(&d, n) {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+    if x == 1 || x == n_minus_1 {
+        return true;
+    }
+    for _ in 1..s {
+        x.square_mut();
+        x %= n;
+        if x == n_minus_1 {
+            return true;
+        }
+    }
+    false
+}
+
+/// 模 n 下除以 2 (n 为奇数)
+fn half_mod(mut x: Integer, n: &Integer) -> Integer {
+    if x.is_odd() {
+        x += n;
+    }
+    x >>= 1;
+    x.rem_euc(n)
+}
+
+/// 强 Lucas 概率素数测试 (Selfridge Method A 选取参数 D, P=1, Q=(1-D)/4)
+fn is_strong_lucas_probable_prime(n: &Integer) -> bool {
+    // 完全平方数不存在 Jacobi(D/n) = -1 的 D，必须先排除
+    if n.is_perfect_square() {
+        return false;
+    }
+
+    let mut d_abs: i64 = 5;
+    let mut sign: i64 = 1;
+    let d = loop {
+        let d = Integer::from(d_abs * sign);
+        let j = d.jacobi(n);
+        if j == -1 {
+            break d;
+        }
+        if j == 0 && *n != d_abs {
+            return false;
+        }
+        d_abs += 2;
+        sign = -sign;
+    };
+    let p = Integer::from(1);
+    let q: Integer = (Integer::from(1) - &d) / 4;
+
+    // n + 1 = k * 2^s
+    let n_plus_1 = Integer::from(n + 1u32);
+    let s = n_plus_1.find_one(0).unwrap_or(0);
+    let k = Integer::from(&n_plus_1 >> s);
+
+    let d_mod = d.clone().rem_euc(n);
+    let q_mod = q.clone().rem_euc(n);
+
+    // 二进制 Lucas 链：从最高位开始，U_1 = 1, V_1 = P, Q^1 = Q
+    let mut u = Integer::from(1);
+    let mut v = p.clone();
+    let mut qk = q_mod.clone();
+    let bits = k.significant_bits();
+    for i in (0..bits.saturating_sub(1)).rev() {
+        // 倍增：U_2m = U_m V_m, V_2m = V_m^2 - 2 Q^m
+        u = Integer::from(&u * &v).rem_euc(n);
+        v = (Integer::from(v.square_ref()) - Integer::from(&qk * 2u32)).rem_euc(n);
+        qk = Integer::from(qk.square_ref()).rem_euc(n);
+
+        if k.get_bit(i) {
+            // 加一：U_{m+1} = (P U + V)/2, V_{m+1} = (D U + P V)/2
+            let u_next = half_mod(Integer::from(&p * &u) + &v, n);
+            let v_next = half_mod(Integer::from(&d_mod * &u) + Integer::from(&p * &v), n);
+            u = u_next;
+            v = v_next;
+            qk = Integer::from(&qk * &q_mod).rem_euc(n);
+        }
+    }
+
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = (Integer::from(v.square_ref()) - Integer::from(&qk * 2u32)).rem_euc(n);
+        if v == 0 {
+            return true;
+        }
+        qk = Integer::from(qk.square_ref()).rem_euc(n);
+    }
+    false
+}
+
+/// Baillie-PSW 素性测试
+pub fn is_baillie_psw_prime(n: &Integer) -> bool {
+    if *n < 2 {
+        return false;
+    }
+    if *n < SMALL_PRIME_LIMIT {
+        return *n == 2 || (n.is_odd() && small_primes().binary_search(&n.to_u32().unwrap_or(0)).is_ok());
+    }
+    if !passes_small_prime_sieve(n) {
+        return false;
+    }
+    is_strong_probable_prime(n, 2) && is_strong_lucas_probable_prime(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 以 2 为底的强伪素数 (OEIS A001262)
+    const STRONG_PSEUDOPRIMES_BASE_2: [u32; 6] = [2047, 3277, 4033, 4681, 8321, 15841];
+    // 强 Lucas 伪素数 (Selfridge 参数，OEIS A217255)
+    const STRONG_LUCAS_PSEUDOPRIMES: [u32; 5] = [5459, 5777, 10877, 16109, 18971];
+
+    #[test]
+    fn strong_base_2_pseudoprimes_fail_lucas() {
+        for n in STRONG_PSEUDOPRIMES_BASE_2 {
+            let n = Integer::from(n);
+            assert!(is_strong_probable_prime(&n, 2), "{} is a base-2 strong pseudoprime", n);
+            assert!(!is_strong_lucas_probable_prime(&n), "{} must fail the Lucas test", n);
+            assert!(!is_baillie_psw_prime(&n));
+        }
+    }
+
+    #[test]
+    fn strong_lucas_pseudoprimes_fail_base_2() {
+        for n in STRONG_LUCAS_PSEUDOPRIMES {
+            let n = Integer::from(n);
+            assert!(is_strong_lucas_probable_prime(&n), "{} is a strong Lucas pseudoprime", n);
+            assert!(!is_strong_probable_prime(&n, 2), "{} must fail the base-2 test", n);
+            assert!(!is_baillie_psw_prime(&n));
+        }
+    }
+
+    #[test]
+    fn baillie_psw_agrees_with_gmp() {
+        assert!(is_baillie_psw_prime(&((Integer::from(1) << 127) - 1u32)));
+        assert!(!is_baillie_psw_prime(&((Integer::from(1) << 128) + 1u32)));
+        // 完全平方数 (Lucas 参数搜索无法终止的情形)
+        assert!(!is_baillie_psw_prime(&Integer::from(Integer::from(65537).square_ref())));
+
+        let start = Integer::from(1) << 64;
+        for i in 0..2000u32 {
+            let n = Integer::from(&start + i);
+            let expected = n.is_probably_prime(40) != rug::integer::IsPrime::No;
+            assert_eq!(is_baillie_psw_prime(&n), expected, "disagreement at {}", n);
+        }
+    }
+
+    #[test]
+    fn sieve_rejects_small_factors() {
+        assert!(passes_small_prime_sieve(&Integer::from(32749)));
+        assert!(!passes_small_prime_sieve(&Integer::from(32749u64 * 65537)));
+        assert!(!passes_small_prime_sieve(&Integer::from(1u64 << 40)));
+    }
+}
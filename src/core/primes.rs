@@ -2,35 +2,241 @@
 
 use rug::Integer;
 use blake3::Hasher;
+use sha2::{Digest, Sha256};
+use serde::{Serialize, Deserialize};
+use rayon::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use super::primality::{PrimalityTest, passes_small_prime_sieve};
 
-pub fn hash_to_prime(user_id: &str, bit_size: u32) -> Result<Integer, String> {
-    let mut nonce = 0u64;
-    // [SECURITY FIX]: 降低尝试次数，防止 素数搜索 CPU DoS
-    let max_attempts = 500; 
-    
-    while nonce < max_attempts {
-        let mut hasher = Hasher::new();
+// [SECURITY FIX]: 降低尝试次数，防止 素数搜索 CPU DoS
+pub const MAX_PRIME_ATTEMPTS: u64 = 500;
+
+/// 代表元位长范围：低于 64 bits 碰撞概率不可接受，高于 256 bits 超出单次哈希输出
+pub const MIN_PRIME_BITS: u32 = 64;
+pub const MAX_PRIME_BITS: u32 = 256;
+
+pub const DEFAULT_DOMAIN_TAG: &str = "htp:hash-to-prime:v1";
+
+/// 用户的素数代表元，附带搜索时首个命中的 nonce；验证方只重算该 nonce 处的候选数，不重放之前的尝试
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrimeRepresentative {
+    pub prime: Integer,
+    pub nonce: u64,
+}
+
+/// Hash-to-Prime 使用的哈希函数
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrimeHashFunction {
+    Blake3,
+    /// 规范 (SPECIFICATION.md §1.1) 中描述的 SHA-256
+    Sha256,
+}
+
+impl fmt::Display for PrimeHashFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrimeHashFunction::Blake3 => f.write_str("blake3"),
+            PrimeHashFunction::Sha256 => f.write_str("sha256"),
+        }
+    }
+}
+
+impl FromStr for PrimeHashFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blake3" => Ok(PrimeHashFunction::Blake3),
+            "sha256" | "sha-256" => Ok(PrimeHashFunction::Sha256),
+            other => Err(format!("Unknown hash function '{}'. Expected 'blake3' or 'sha256'.", other)),
+        }
+    }
+}
+
+/// Hash-to-Prime 参数：随张量参数一起持久化，服务端、CLI 与验证方必须一致
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrimeHashConfig {
+    pub hash: PrimeHashFunction,
+    /// 域分离标签；为空时退化为旧版 (仅长度前缀) 派生方式
+    pub domain_tag: Vec<u8>,
+    pub bit_size: u32,
+    pub primality: PrimalityTest,
+}
+
+impl Default for PrimeHashConfig {
+    fn default() -> Self {
+        PrimeHashConfig {
+            hash: PrimeHashFunction::Blake3,
+            domain_tag: DEFAULT_DOMAIN_TAG.as_bytes().to_vec(),
+            bit_size: 128,
+            primality: PrimalityTest::default(),
+        }
+    }
+}
+
+impl PrimeHashConfig {
+    pub fn new(hash: PrimeHashFunction, domain_tag: &str, bit_size: u32) -> Result<Self, String> {
+        let config = PrimeHashConfig {
+            hash,
+            domain_tag: domain_tag.as_bytes().to_vec(),
+            bit_size,
+            primality: PrimalityTest::default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 旧版派生：blake3、无域标签，位长不受 64-256 限制
+    pub fn legacy(bit_size: u32) -> Self {
+        PrimeHashConfig {
+            hash: PrimeHashFunction::Blake3,
+            domain_tag: Vec::new(),
+            bit_size,
+            primality: PrimalityTest::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bit_size < MIN_PRIME_BITS || self.bit_size > MAX_PRIME_BITS {
+            return Err(format!("Invalid prime size {} bits. Must be between {} and {}.",
+                self.bit_size, MIN_PRIME_BITS, MAX_PRIME_BITS));
+        }
+        if self.domain_tag.len() > 255 {
+            return Err("Domain tag too long (max 255 bytes).".to_string());
+        }
+        Ok(())
+    }
+
+    /// 由 (user_id, nonce) 确定性地导出候选数 (最高位与最低位已置 1)
+    fn derive_candidate(&self, user_id: &str, nonce: u64) -> Integer {
         // [SECURITY FIX]: 增加长度前缀，防止 Canonicalization (哈希拼接) 攻击
-        hasher.update(&(user_id.len() as u64).to_le_bytes());
-        hasher.update(user_id.as_bytes());
-        hasher.update(&nonce.to_le_bytes());
-        let hash = hasher.finalize();
+        let mut input = Vec::with_capacity(8 + self.domain_tag.len() + 8 + user_id.len() + 8);
+        if !self.domain_tag.is_empty() {
+            input.extend_from_slice(&(self.domain_tag.len() as u64).to_le_bytes());
+            input.extend_from_slice(&self.domain_tag);
+        }
+        input.extend_from_slice(&(user_id.len() as u64).to_le_bytes());
+        input.extend_from_slice(user_id.as_bytes());
+        input.extend_from_slice(&nonce.to_le_bytes());
+
+        let digest: [u8; 32] = match self.hash {
+            PrimeHashFunction::Blake3 => {
+                let mut hasher = Hasher::new();
+                hasher.update(&input);
+                *hasher.finalize().as_bytes()
+            },
+            PrimeHashFunction::Sha256 => Sha256::digest(&input).into(),
+        };
 
-        let mut candidate = Integer::from_digits(hash.as_bytes(), rug::integer::Order::Lsf);
-        candidate.set_bit(bit_size - 1, true);
+        let mut candidate = Integer::from_digits(&digest, rug::integer::Order::Lsf);
+        // [FIX]: 截断到 bit_size 位，否则 "64-bit" 代表元实际是 256-bit，位长无法由素数本身确定
+        candidate.keep_bits_mut(self.bit_size);
+        candidate.set_bit(self.bit_size - 1, true);
         candidate.set_bit(0, true);
+        candidate
+    }
+
+    pub fn hash_to_prime(&self, user_id: &str) -> Result<PrimeRepresentative, String> {
+        let mut nonce = 0u64;
+
+        while nonce < MAX_PRIME_ATTEMPTS {
+            let candidate = self.derive_candidate(user_id, nonce);
+
+            // [PERF]: 先用预计算的小素数表筛掉绝大多数合数，再进入昂贵的概率测试
+            if self.is_prime_candidate(&candidate) {
+                return Ok(PrimeRepresentative { prime: candidate, nonce });
+            }
 
-        if candidate.mod_u(3) == 0 || candidate.mod_u(5) == 0 {
             nonce += 1;
-            continue;
         }
 
-        if candidate.is_probably_prime(25) != rug::integer::IsPrime::No {
-            return Ok(candidate);
+        Err(format!("❌ Failed to generate prime for user after {} attempts.", MAX_PRIME_ATTEMPTS))
+    }
+
+    /// 批量生成：在 rayon 线程池上并行搜索，结果顺序与输入一致
+    pub fn hash_to_primes_parallel(&self, user_ids: &[&str]) -> Vec<Result<PrimeRepresentative, String>> {
+        user_ids
+            .par_iter()
+            .map(|uid| self.hash_to_prime(uid))
+            .collect()
+    }
+
+    /// 快速验证：重算 nonce 处的单个候选数并做一次素性测试，无需重复整个搜索过程
+    /// 不检查 nonce 是否为首个命中 (那需要对之前的每个 nonce 各测一次)：代表元由节点自行搜索并随成员记录保存，
+    /// 注册方无法挑选 nonce，同一 ID 在张量中只对应一条成员记录。
+    pub fn verify_representative(&self, user_id: &str, nonce: u64, prime: &Integer) -> bool {
+        if nonce >= MAX_PRIME_ATTEMPTS || prime.significant_bits() != self.bit_size {
+            return false;
         }
 
-        nonce += 1;
+        let candidate = self.derive_candidate(user_id, nonce);
+        &candidate == prime && self.is_prime_candidate(&candidate)
+    }
+
+    fn is_prime_candidate(&self, candidate: &Integer) -> bool {
+        passes_small_prime_sieve(candidate) && self.primality.is_probable_prime(candidate)
+    }
+}
+
+pub fn hash_to_prime(user_id: &str, bit_size: u32) -> Result<PrimeRepresentative, String> {
+    PrimeHashConfig::legacy(bit_size).hash_to_prime(user_id)
+}
+
+pub fn hash_to_prime_with(user_id: &str, bit_size: u32, test: PrimalityTest) -> Result<PrimeRepresentative, String> {
+    PrimeHashConfig { primality: test, ..PrimeHashConfig::legacy(bit_size) }.hash_to_prime(user_id)
+}
+
+pub fn hash_to_primes_parallel(user_ids: &[&str], bit_size: u32) -> Vec<Result<PrimeRepresentative, String>> {
+    PrimeHashConfig::legacy(bit_size).hash_to_primes_parallel(user_ids)
+}
+
+pub fn hash_to_primes_parallel_with(user_ids: &[&str], bit_size: u32, test: PrimalityTest) -> Vec<Result<PrimeRepresentative, String>> {
+    PrimeHashConfig { primality: test, ..PrimeHashConfig::legacy(bit_size) }.hash_to_primes_parallel(user_ids)
+}
+
+/// 单候选验证，见 [`PrimeHashConfig::verify_representative`]
+pub fn verify_prime_representative(user_id: &str, nonce: u64, prime: &Integer) -> bool {
+    verify_prime_representative_with(user_id, nonce, prime, PrimalityTest::default())
+}
+
+pub fn verify_prime_representative_with(user_id: &str, nonce: u64, prime: &Integer, test: PrimalityTest) -> bool {
+    // 候选数最高位固定为 1，因此位长可直接从 prime 读出
+    let bit_size = prime.significant_bits();
+    if bit_size < 2 {
+        return false;
+    }
+    PrimeHashConfig { primality: test, ..PrimeHashConfig::legacy(bit_size) }.verify_representative(user_id, nonce, prime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn representative_round_trip() {
+        let config = PrimeHashConfig::default();
+        let rep = config.hash_to_prime("alice").unwrap();
+        assert_eq!(rep.prime.significant_bits(), config.bit_size);
+        assert!(config.verify_representative("alice", rep.nonce, &rep.prime));
+        assert!(!config.verify_representative("bob", rep.nonce, &rep.prime));
+    }
+
+    #[test]
+    fn verification_checks_only_the_given_candidate() {
+        let config = PrimeHashConfig::default();
+        let rep = config.hash_to_prime("alice").unwrap();
+        // 同一 nonce 下的合数候选、其他 nonce 的候选均被拒绝
+        let composite = (0..MAX_PRIME_ATTEMPTS)
+            .find(|&n| !config.is_prime_candidate(&config.derive_candidate("alice", n)))
+            .expect("a composite candidate within the attempt limit");
+        assert!(!config.verify_representative("alice", composite, &config.derive_candidate("alice", composite)));
+        assert!(!config.verify_representative("alice", rep.nonce + 1, &rep.prime));
+        assert!(!config.verify_representative("alice", MAX_PRIME_ATTEMPTS, &rep.prime));
+        // 之后的素数候选同样通过：验证只测给定的候选，不重放之前的 nonce
+        let later = (rep.nonce + 1..MAX_PRIME_ATTEMPTS)
+            .find(|&n| config.is_prime_candidate(&config.derive_candidate("alice", n)))
+            .expect("a second prime candidate within the attempt limit");
+        assert!(config.verify_representative("alice", later, &config.derive_candidate("alice", later)));
     }
-    
-    Err(format!("❌ Failed to generate prime for user after {} attempts.", max_attempts))
 }
@@ -1,3 +1,4 @@
 pub mod core;
 pub mod topology;
 pub mod net;
+pub mod storage;
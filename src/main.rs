@@ -3,11 +3,12 @@
 mod core;
 mod topology;
 mod net; // 引入 net 以便 cargo check 能通过
+mod storage;
 
 use crate::core::param::SystemParameters;
-use crate::core::primes::hash_to_prime;
 use crate::core::affine::AffineTuple;
 use crate::topology::tensor::HyperTensor;
+use crate::topology::membership::InsertOutcome;
 
 fn main() {
     println!("=== Hyper-Tensor Protocol (Secure Showcase) ===");
@@ -21,8 +22,8 @@ fn main() {
     let user_ids = vec!["Alice_001", "Bob_002", "Charlie_003"];
 
     for uid in user_ids {
-        let p = match hash_to_prime(uid, 64) {
-            Ok(prime) => prime,
+        let rep = match tensor.prime_config.hash_to_prime(uid) {
+            Ok(rep) => rep,
             Err(e) => { eprintln!("⚠️  Skipping {}: {}", uid, e); continue; }
         };
         
@@ -31,11 +32,12 @@ fn main() {
         let g = crate::core::algebra::ClassGroupElement::generator(&params.discriminant);
         
         let tuple = AffineTuple {
-            p_factor: p.clone(),
+            p_factor: rep.prime,
             q_shift: g, 
         };
 
-        match tensor.insert(uid, tuple) {
+        match tensor.insert(uid, tuple, rep.nonce) {
+            Ok(InsertOutcome::AlreadyRegistered { .. }) => println!("[Ingest] User {} already registered, skipped.", uid),
             Ok(_) => println!("[Ingest] User {} mapped (Non-commutative).", uid),
             Err(e) => eprintln!("❌ Insert Failed: {}", e),
         }
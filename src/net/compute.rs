@@ -0,0 +1,84 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OnceCell, Semaphore};
+
+/// [PERF FIX]: 专用计算池
+/// GMP 运算 (Hash-to-Prime、折叠、证明构造) 在阻塞线程上执行，不占用 QUIC 运行时的异步工作线程；
+/// 并发任务数受许可数限制，过载时请求排队而不是无限创建阻塞线程。
+pub struct ComputePool {
+    permits: Arc<Semaphore>,
+}
+
+impl ComputePool {
+    /// workers = 0 时使用逻辑 CPU 数
+    pub fn new(workers: usize) -> Self {
+        let workers = match workers {
+            0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            n => n,
+        };
+        ComputePool { permits: Arc::new(Semaphore::new(workers)) }
+    }
+
+    pub async fn run<T, F>(&self, job: F) -> Result<T, String>
+    where
+        F: FnOnce() -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self.permits.clone().acquire_owned().await.map_err(|_| "Compute pool closed.".to_string())?;
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            job()
+        }).await.map_err(|e| format!("Compute task failed: {}", e))?
+    }
+}
+
+impl Default for ComputePool {
+    fn default() -> Self {
+        ComputePool::new(0)
+    }
+}
+
+/// [PERF FIX]: 单飞 (single-flight) 合并
+/// 同一键的并发请求只执行一次计算，其余请求等待并共享结果；计算完成后条目即移除，
+/// 因此结果不会过期 (键中应包含 epoch 等版本信息)。
+pub struct SingleFlight<K, V> {
+    inflight: Mutex<HashMap<K, Flight<V>>>,
+}
+
+type Flight<V> = Arc<OnceCell<Result<V, String>>>;
+
+impl<K: Hash + Eq + Clone, V: Clone> SingleFlight<K, V> {
+    pub fn new() -> Self {
+        SingleFlight { inflight: Mutex::new(HashMap::new()) }
+    }
+
+    pub async fn run<F, Fut>(&self, key: K, compute: F) -> Result<V, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, String>>,
+    {
+        let cell = {
+            let mut inflight = self.inflight.lock().map_err(|_| "Single-flight lock poisoned.".to_string())?;
+            inflight.entry(key.clone()).or_insert_with(|| Arc::new(OnceCell::new())).clone()
+        };
+        // 首个调用者执行计算；若其被取消，下一个等待者接手
+        let result = cell.get_or_init(compute).await.clone();
+
+        if let Ok(mut inflight) = self.inflight.lock() {
+            if inflight.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+                inflight.remove(&key);
+            }
+        }
+        result
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        SingleFlight::new()
+    }
+}
@@ -0,0 +1,265 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::MAX_P_FACTOR_BITS;
+use crate::core::param::{SecurityProfile, MAX_DISCRIMINANT_BITS, MIN_DISCRIMINANT_BITS};
+use crate::core::primes::{PrimeHashConfig, PrimeHashFunction, DEFAULT_DOMAIN_TAG};
+use crate::storage::group_commit::{GroupCommitConfig, DEFAULT_COMMIT_BATCH, DEFAULT_COMMIT_DELAY};
+use crate::storage::store::StoreBackend;
+use crate::storage::DEFAULT_SNAPSHOT_INTERVAL;
+use crate::topology::placement::{AddressingMode, FullCellPolicy, Placement};
+use crate::topology::tensor::HyperTensor;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 默认张量边长 L
+pub const DEFAULT_SIDE_LENGTH: usize = 100;
+/// 最大维度 (防止零维/超高维张量)
+pub const MAX_DIMENSIONS: usize = 20;
+
+/// [NEW FEATURE]: 节点配置
+/// 可由 TOML 配置文件加载 (缺省字段取默认值)，命令行参数再逐项覆盖；
+/// 几何、判别式与 Hash-to-Prime 参数仅在创建新张量时生效，已有数据库以存储的参数为准。
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    pub bind: String,
+    /// 判别式生成种子 (必填)
+    pub seed: Option<String>,
+    pub dim: usize,
+    pub side_length: usize,
+    #[serde(deserialize_with = "from_str")]
+    pub profile: SecurityProfile,
+    /// 覆盖安全档位的判别式位长
+    pub discriminant_bits: Option<u32>,
+    #[serde(deserialize_with = "from_str")]
+    pub prime_hash: PrimeHashFunction,
+    pub prime_tag: String,
+    pub prime_bits: u32,
+    /// 创建新张量时导出判别式证书的路径
+    pub discriminant_cert: Option<PathBuf>,
+    /// 成员寻址方式 (sequential: 注册时依次分配数字序号)
+    #[serde(deserialize_with = "from_str")]
+    pub addressing: AddressingMode,
+    /// 以随机的部署级密钥计算坐标 (关闭时为公开映射，可被离线碾磨)
+    pub keyed_coords: bool,
+    /// 每个单元格的成员上限；0 表示按 P-factor 上限推导
+    pub max_cell_members: u32,
+    #[serde(deserialize_with = "from_str")]
+    pub full_cell: FullCellPolicy,
+    #[serde(deserialize_with = "from_str")]
+    pub store: StoreBackend,
+    /// 快照、WAL 与单元格日志所在目录
+    pub data_dir: PathBuf,
+    pub snapshot_interval: u64,
+    pub commit_delay_ms: u64,
+    pub commit_batch: usize,
+    /// 0 表示逻辑 CPU 数
+    pub compute_workers: usize,
+    pub operator_secret: Option<String>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            bind: "127.0.0.1:4433".to_string(),
+            seed: None,
+            dim: 4,
+            side_length: DEFAULT_SIDE_LENGTH,
+            profile: SecurityProfile::Standard2048,
+            discriminant_bits: None,
+            prime_hash: PrimeHashFunction::Blake3,
+            prime_tag: DEFAULT_DOMAIN_TAG.to_string(),
+            prime_bits: 128,
+            discriminant_cert: None,
+            addressing: AddressingMode::Hashed,
+            keyed_coords: true,
+            max_cell_members: 0,
+            full_cell: FullCellPolicy::Probe,
+            store: StoreBackend::Memory,
+            data_dir: PathBuf::from("."),
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            commit_delay_ms: DEFAULT_COMMIT_DELAY.as_millis() as u64,
+            commit_batch: DEFAULT_COMMIT_BATCH,
+            compute_workers: 0,
+            operator_secret: None,
+        }
+    }
+}
+
+impl NodeConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// 启动前校验全部参数
+    pub fn validate(&self) -> Result<(), String> {
+        self.seed.as_deref().filter(|s| !s.is_empty()).ok_or("A seed is required (--seed or `seed` in the config file).")?;
+        // [SECURITY FIX]: 输入参数校验，防止零维黑洞 (Zero-Dimension Singularity)
+        if self.dim == 0 || self.dim > MAX_DIMENSIONS {
+            return Err(format!("Invalid dimension {}. Must be between 1 and {}.", self.dim, MAX_DIMENSIONS));
+        }
+        // 坐标由 128-bit 哈希值逐维取模得到，并打包为 u128：L^d 必须落在该空间内
+        HyperTensor::check_geometry(self.dim, self.side_length)?;
+        let bits = self.discriminant_bits();
+        if !(MIN_DISCRIMINANT_BITS..=MAX_DISCRIMINANT_BITS).contains(&bits) {
+            return Err(format!(
+                "Invalid discriminant size {} bits. Must be between {} and {}.", bits, MIN_DISCRIMINANT_BITS, MAX_DISCRIMINANT_BITS
+            ));
+        }
+        self.prime_config()?;
+        let ceiling = MAX_P_FACTOR_BITS / self.prime_bits;
+        if self.max_cell_members > ceiling {
+            return Err(format!(
+                "max_cell_members {} exceeds {} ({}-bit P-factor limit / {}-bit primes).", self.max_cell_members, ceiling, MAX_P_FACTOR_BITS, self.prime_bits
+            ));
+        }
+        if self.snapshot_interval == 0 {
+            return Err("snapshot_interval must be at least 1.".to_string());
+        }
+        if self.commit_batch == 0 {
+            return Err("commit_batch must be at least 1.".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn seed(&self) -> &str {
+        self.seed.as_deref().unwrap_or_default()
+    }
+
+    /// 显式位长优先于安全档位
+    pub fn discriminant_bits(&self) -> u32 {
+        self.discriminant_bits.unwrap_or_else(|| self.profile.discriminant_bits())
+    }
+
+    pub fn prime_config(&self) -> Result<PrimeHashConfig, String> {
+        PrimeHashConfig::new(self.prime_hash, &self.prime_tag, self.prime_bits)
+    }
+
+    /// 新张量的坐标映射与占用策略 (启用时生成新密钥)
+    pub fn placement(&self) -> Result<Placement, String> {
+        if self.keyed_coords {
+            Placement::keyed(self.max_cell_members, self.full_cell)
+        } else {
+            Ok(Placement { coord_key: None, max_cell_members: self.max_cell_members, full_cell: self.full_cell })
+        }
+    }
+
+    pub fn group_commit(&self) -> GroupCommitConfig {
+        GroupCommitConfig { max_delay: Duration::from_millis(self.commit_delay_ms), max_batch: self.commit_batch }
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.data_dir.join("htp_tensor.db")
+    }
+
+    pub fn wal_path(&self) -> PathBuf {
+        self.data_dir.join("htp_tensor.wal")
+    }
+
+    pub fn cells_dir(&self) -> PathBuf {
+        self.data_dir.join("htp_cells")
+    }
+}
+
+/// 枚举字段在配置文件中与命令行使用相同的文本形式
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::testing::Scratch;
+
+    fn seeded() -> NodeConfig {
+        NodeConfig { seed: Some("htp:config:test".to_string()), ..NodeConfig::default() }
+    }
+
+    #[test]
+    fn toml_file_overlays_the_defaults() {
+        let dir = Scratch::new("config-overlay");
+        let path = dir.0.join("node.toml");
+        std::fs::write(&path, r#"
+            seed = "from-file"
+            side_length = 178
+            profile = "test-1024"
+            prime_hash = "SHA256"
+            addressing = "sequential"
+            full_cell = "reject"
+            max_cell_members = 8
+        "#).unwrap();
+
+        let config = NodeConfig::load(&path).unwrap();
+        assert_eq!(config.seed(), "from-file");
+        assert_eq!(config.side_length, 178);
+        assert_eq!(config.profile, SecurityProfile::Test1024);
+        assert_eq!(config.discriminant_bits(), 1024);
+        assert_eq!(config.prime_hash, PrimeHashFunction::Sha256);
+        assert_eq!(config.addressing, AddressingMode::Sequential);
+        assert_eq!(config.full_cell, FullCellPolicy::Reject);
+        assert_eq!(config.max_cell_members, 8);
+        // 文件中未出现的字段保持默认值
+        let defaults = NodeConfig::default();
+        assert_eq!((config.bind.as_str(), config.dim, config.prime_bits), (defaults.bind.as_str(), defaults.dim, defaults.prime_bits));
+        assert_eq!((config.keyed_coords, config.commit_batch, config.store), (defaults.keyed_coords, defaults.commit_batch, defaults.store));
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn malformed_toml_is_rejected() {
+        let dir = Scratch::new("config-malformed");
+        let path = dir.0.join("node.toml");
+        for text in ["sead = \"typo\"", "profile = \"huge\"", "full_cell = \"evict\"", "dim = \"four\""] {
+            std::fs::write(&path, text).unwrap();
+            assert!(NodeConfig::load(&path).is_err(), "{}", text);
+        }
+        let missing = NodeConfig::load(&dir.0.join("absent.toml")).unwrap_err();
+        assert!(missing.contains("absent.toml"), "{}", missing);
+    }
+
+    #[test]
+    fn geometry_must_fit_the_coordinate_space() {
+        seeded().validate().unwrap();
+        assert!(NodeConfig { seed: None, ..seeded() }.validate().is_err());
+        for dim in [0, MAX_DIMENSIONS + 1] {
+            assert!(NodeConfig { dim, ..seeded() }.validate().is_err(), "dim {}", dim);
+        }
+        assert!(NodeConfig { side_length: 1, ..seeded() }.validate().is_err());
+
+        // 100^19 < 2^128 < 100^20；65536^8 = 2^128 恰好越界
+        NodeConfig { dim: 19, side_length: 100, ..seeded() }.validate().unwrap();
+        assert!(NodeConfig { dim: 20, side_length: 100, ..seeded() }.validate().is_err());
+        NodeConfig { dim: 7, side_length: 1 << 16, ..seeded() }.validate().unwrap();
+        assert!(NodeConfig { dim: 8, side_length: 1 << 16, ..seeded() }.validate().is_err());
+    }
+
+    #[test]
+    fn max_cell_members_is_bounded_by_the_p_factor() {
+        for prime_bits in [64, 128, 256] {
+            let ceiling = MAX_P_FACTOR_BITS / prime_bits;
+            NodeConfig { prime_bits, max_cell_members: ceiling, ..seeded() }.validate().unwrap();
+            assert!(NodeConfig { prime_bits, max_cell_members: ceiling + 1, ..seeded() }.validate().is_err(), "{} bits", prime_bits);
+        }
+        assert!(NodeConfig { prime_bits: 32, ..seeded() }.validate().is_err());
+    }
+
+    #[test]
+    fn discriminant_bits_must_be_in_range() {
+        for bits in [0, MIN_DISCRIMINANT_BITS - 1, MAX_DISCRIMINANT_BITS + 1, u32::MAX] {
+            assert!(NodeConfig { discriminant_bits: Some(bits), ..seeded() }.validate().is_err(), "{} bits", bits);
+        }
+        for bits in [MIN_DISCRIMINANT_BITS, MAX_DISCRIMINANT_BITS] {
+            NodeConfig { discriminant_bits: Some(bits), ..seeded() }.validate().unwrap();
+        }
+        // 显式位长覆盖安全档位
+        let config = NodeConfig { profile: SecurityProfile::High3072, discriminant_bits: Some(1536), ..seeded() };
+        assert_eq!(config.discriminant_bits(), 1536);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod compute;
+pub mod config;
 pub mod service;
 pub mod transport;
 pub mod wire;
@@ -8,17 +8,75 @@ use bincode::{self, Options};
 use blake3;
 use log::{info, warn, error};
 
-use crate::topology::tensor::HyperTensor;
-use crate::net::wire::{HtpRequest, HtpResponse, RequestHeader};
+use crate::topology::tensor::{batch_primes, HyperTensor};
+use crate::topology::snapshot::EpochSnapshots;
+use crate::topology::membership::{InsertOutcome, RegistrationPolicy, MAX_BATCH_SIZE};
+use crate::net::wire::{HtpRequest, HtpResponse, OperatorAuth, RequestHeader};
 use crate::core::affine::AffineTuple;
+use crate::storage::group_commit::{GroupCommitter, Rebuild, Receipt};
+use crate::storage::format;
+use crate::storage::wal::WalOp;
+use crate::net::compute::{ComputePool, SingleFlight};
+use tokio::sync::oneshot;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 
-pub async fn run_prover_service(endpoint: Endpoint, tensor: Arc<RwLock<HyperTensor>>) {
+/// 服务的运行时配置
+pub struct ServiceContext {
+    /// None 时拒绝所有需要运维授权的请求 (如 RevokeUser)
+    pub operator: Option<OperatorAuth>,
+    /// 后台持久化任务 (组提交)；None 时为纯内存节点 (不落盘)
+    pub committer: Option<GroupCommitter>,
+    /// 最新已落盘 epoch 的只读快照，证明与根查询只读它，不占用张量锁
+    pub snapshots: Arc<EpochSnapshots>,
+    /// 折叠与证明构造的专用计算池
+    pub compute: ComputePool,
+    /// 按 (epoch, user_id) 合并并发的证明请求
+    pub proofs: SingleFlight<(u64, String), HtpResponse>,
+    /// 时间窗口内已接受的运维请求
+    pub operator_requests: ReplayCache,
+}
+
+/// 请求时间戳允许的偏差 (秒)
+pub const REQUEST_WINDOW_SECS: u64 = 60;
+
+/// [SECURITY FIX]: 运维请求的 request_id 在时间窗口内只接受一次
+/// 窗口外的请求已被 validate_header 拒绝，因此只需保留窗口内的记录。
+/// 仅在 MAC 校验通过后登记，未授权方无法占用或撑大缓存。
+#[derive(Default)]
+pub struct ReplayCache {
+    seen: std::sync::Mutex<HashMap<u64, u64>>,
+}
+
+impl ReplayCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 首次出现返回 true；重复的 request_id 返回 false
+    pub fn admit(&self, header: &RequestHeader) -> bool {
+        let now = unix_now();
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, timestamp| *timestamp + REQUEST_WINDOW_SECS >= now);
+        match seen.entry(header.request_id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(slot) => {
+                slot.insert(header.timestamp);
+                true
+            },
+        }
+    }
+}
+
+pub async fn run_prover_service(endpoint: Endpoint, tensor: Arc<RwLock<HyperTensor>>, ctx: ServiceContext) {
+    let ctx = Arc::new(ctx);
     // [SECURITY FIX]: 限制最大并发连接数，防止 连接风暴 DoS
     let limit = Arc::new(Semaphore::new(10_000));
 
     while let Some(conn) = endpoint.accept().await {
         let permit = limit.clone().acquire_owned().await.unwrap();
         let tensor_ref = tensor.clone();
+        let ctx_ref = ctx.clone();
         
         tokio::spawn(async move {
             let _permit = permit; // 自动释放许可
@@ -32,8 +90,9 @@ pub async fn run_prover_service(endpoint: Endpoint, tensor: Arc<RwLock<HyperTens
 
             while let Ok((send, recv)) = connection.accept_bi().await {
                 let t = tensor_ref.clone();
+                let c = ctx_ref.clone();
                 tokio::spawn(async move {
-                    if let Err(e) = handle_stream(t, send, recv).await {
+                    if let Err(e) = handle_stream(t, c, send, recv).await {
                         warn!("[Net] Stream handled with error: {}", e);
                     }
                 });
@@ -50,6 +109,7 @@ fn sanitize_error(e: String) -> String {
 
 async fn handle_stream(
     tensor: Arc<RwLock<HyperTensor>>, 
+    ctx: Arc<ServiceContext>,
     mut send: SendStream, 
     mut recv: RecvStream
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -72,7 +132,7 @@ async fn handle_stream(
         Err(e) => return Err(Box::new(e)),
     };
 
-    let response = match process_request(&tensor, request).await {
+    let response = match process_request(&tensor, &ctx, request).await {
         Ok(resp) => resp,
         Err(e) => HtpResponse::Error(sanitize_error(e)),
     };
@@ -89,96 +149,503 @@ fn validate_header(header: &RequestHeader) -> Result<(), String> {
         return Err(format!("Protocol Mismatch: Server v{}, Client v{}", 
             crate::net::wire::PROTOCOL_VERSION, header.version));
     }
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let now = unix_now();
     // 简单的防重放：拒绝 60 秒以外的请求
-    if header.timestamp < now.saturating_sub(60) || header.timestamp > now + 60 {
+    if header.timestamp < now.saturating_sub(REQUEST_WINDOW_SECS) || header.timestamp > now + REQUEST_WINDOW_SECS {
         return Err("Request expired or time skew too large".to_string());
     }
     Ok(())
 }
 
-async fn process_request(tensor: &Arc<RwLock<HyperTensor>>, request: HtpRequest) -> Result<HtpResponse, String> {
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// 运维 MAC 绑定的部署标识：当前已发布快照的参数指纹
+fn deployment_id(ctx: &ServiceContext) -> Result<[u8; 32], String> {
+    format::param_hash(&ctx.snapshots.load())
+}
+
+/// 校验运维标签并登记 request_id；失败时返回拒绝原因
+fn authorize_operator<F>(ctx: &ServiceContext, header: &RequestHeader, verify: F) -> Result<Option<&'static str>, String>
+where
+    F: FnOnce(&OperatorAuth, &[u8; 32]) -> bool,
+{
+    let deployment = deployment_id(ctx)?;
+    if !ctx.operator.as_ref().is_some_and(|op| verify(op, &deployment)) {
+        return Ok(Some("Unauthorized: operator authorization required."));
+    }
+    if !ctx.operator_requests.admit(header) {
+        return Ok(Some("Rejected: duplicate request_id."));
+    }
+    Ok(None)
+}
+
+/// [FIX]: 在写锁内执行变更并提交；提交前任一步失败都回滚到最近一次提交，内存不与 WAL 分叉
+fn transact<T, F>(tensor: &mut HyperTensor, apply: F) -> Result<T, String>
+where
+    F: FnOnce(&mut HyperTensor) -> Result<T, String>,
+{
+    apply(tensor).inspect_err(|e| {
+        if let Err(undo) = tensor.rollback() {
+            fail_stop(&format!("Rollback after '{}' failed", e), &undo);
+        }
+    })
+}
+
+/// [FIX]: 内存已领先于 WAL 且无法撤回时停机；重启后由快照 + WAL 恢复到最后一次落盘的提交
+fn fail_stop(context: &str, e: &str) -> ! {
+    error!("💀 {}: {}. Stopping the node; restart recovers from the WAL.", context, e);
+    std::process::exit(1);
+}
+
+/// 已提交但尚未持久化的变更，及提交后的冻结快照
+struct Sealed {
+    ops: Vec<WalOp>,
+    frozen: HyperTensor,
+}
+
+/// 取出已提交的变更并冻结快照 (在计算池中、写锁内执行)
+/// epoch 已推进，此后的失败无法回滚，只能停机
+fn seal(tensor: &mut HyperTensor) -> Sealed {
+    let ops = tensor.take_wal_ops();
+    let frozen = tensor.freeze().unwrap_or_else(|e| fail_stop("Failed to freeze a committed epoch", &e));
+    Sealed { ops, frozen }
+}
+
+/// 仍持有写锁时把变更交给持久化任务 (入队顺序即 WAL 顺序)，返回落盘回执
+/// 纯内存节点直接丢弃操作并立即发布快照；持久化任务已停止时停机
+fn persist(ctx: &ServiceContext, sealed: Sealed) -> Receipt {
+    match &ctx.committer {
+        Some(committer) => committer.submit(sealed.ops, Some(sealed.frozen))
+            .unwrap_or_else(|e| fail_stop("Failed to queue a committed epoch", &e)),
+        None => {
+            ctx.snapshots.install(sealed.frozen);
+            let (done, receipt) = oneshot::channel();
+            let _ = done.send(Ok(()));
+            receipt
+        },
+    }
+}
+
+/// 等待组提交落盘 (必须在释放写锁之后调用)
+async fn durable(receipt: Receipt) -> Result<(), String> {
+    receipt.await.map_err(|_| "Persistence task stopped.".to_string())?
+}
+
+/// 基于只读快照构造证明 (在计算池中执行)；request_id 由调用方填入
+fn build_proof(snapshot: &HyperTensor, user_id: &str) -> Result<HtpResponse, String> {
+    let coord = snapshot.map_id_to_coord_hash(user_id);
+    
+    // [SECURITY FIX]: 隐私保护 - 假证明 (Dummy Proof)
+    // 防止成员枚举攻击 (Membership Enumeration)
+    // [FIX]: 以桶内成员摘要判定成员资格，落在已占用单元格中的非成员同样得到假证明
+    let (bucket_depth, bucket_chain) = match snapshot.bucket_chain(user_id)? {
+        Some(chain) => chain,
+        None => {
+            let dummy_path = vec![AffineTuple::identity(&snapshot.discriminant); snapshot.dimensions + 1];
+            // 兄弟节点为公开数据，假见证沿真实路径取值，结构上与真实见证一致
+            // 顺序寻址时 coord 由假序号复算，序号与坐标同真实证明一样落在已分配区间内
+            let witness = snapshot.path_witness(&coord, 0, vec![dummy_path[0].clone()]);
+            return Ok(HtpResponse::ProofBundle {
+                request_id: 0,
+                witness,
+                bucket_chain: vec![dummy_path[0].clone()],
+                primary_path: dummy_path,
+                prime_nonce: 0,
+                bucket_depth: 0,
+                epoch: snapshot.epoch,
+                index: snapshot.sequential_index(user_id),
+                prime_config: snapshot.prime_config.clone(),
+                discriminant: snapshot.discriminant.clone(),
+            });
+        }
+    };
+
+    let path = snapshot.path_aggregates(&coord);
+    // [FIX]: nonce 在注册时随成员记录保存，不再为每个证明重跑 Hash-to-Prime
+    let prime_nonce = snapshot.prime_nonce(user_id)?.ok_or("Membership index out of sync.")?;
+    let witness = snapshot.membership_witness(user_id)?
+        .ok_or("Membership index out of sync.")?;
+    
+    Ok(HtpResponse::ProofBundle {
+        request_id: 0,
+        witness,
+        primary_path: path,
+        prime_nonce,
+        bucket_depth,
+        bucket_chain,
+        epoch: snapshot.epoch,
+        index: snapshot.sequential_index(user_id),
+        prime_config: snapshot.prime_config.clone(),
+        discriminant: snapshot.discriminant.clone(),
+    })
+}
+
+async fn process_request(tensor: &Arc<RwLock<HyperTensor>>, ctx: &ServiceContext, request: HtpRequest) -> Result<HtpResponse, String> {
     match request {
         HtpRequest::GetProof { header, user_id } => {
             validate_header(&header)?;
             
-            // [FIX]: 缓存击穿防护 (Double-Checked Locking)
-            // 防止高并发下的雪崩效应
-            let cached_opt = {
-                let guard = tensor.read().await;
-                guard.cached_root.clone()
-            };
+            // [PERF FIX]: 读取已发布的不可变快照 (根与聚合索引均已预先计算)，
+            // 不再获取张量锁，注册与折叠期间证明请求照常服务
+            let snapshot = ctx.snapshots.load();
+            // [PERF FIX]: 证明在计算池中构造；同一 epoch 对同一 ID 的并发请求只计算一次
+            let key = (snapshot.epoch, user_id.clone());
+            let mut response = ctx.proofs.run(key, || ctx.compute.run(move || build_proof(&snapshot, &user_id))).await?;
+            if let HtpResponse::ProofBundle { request_id, .. } = &mut response {
+                *request_id = header.request_id;
+            }
+            Ok(response)
+        },
+        
+        HtpRequest::GetGlobalRoot { header } => {
+            validate_header(&header)?;
+            let snapshot = ctx.snapshots.load();
+            Ok(HtpResponse::GlobalRoot {
+                root: ctx.snapshots.root()?,
+                prime_config: snapshot.prime_config.clone(),
+                discriminant: snapshot.discriminant.clone(),
+                deployment: format::param_hash(&snapshot)?,
+            })
+        },
 
-            let _root = if let Some(r) = cached_opt {
-                r
-            } else {
-                let mut guard = tensor.write().await;
-                if let Some(r) = &guard.cached_root {
-                    r.clone() // 别的线程已经算好了
-                } else {
-                    info!("🧮 Cache miss. Computing Global Root...");
-                    guard.calculate_global_root()?
-                }
-            };
-            
+        HtpRequest::GetRootAt { header, epoch } => {
+            validate_header(&header)?;
             let guard = tensor.read().await;
-            let coord = guard.map_id_to_coord_hash(&user_id);
-            
-            // [SECURITY FIX]: 隐私保护 - 假证明 (Dummy Proof)
-            // 防止成员枚举攻击 (Membership Enumeration)
-            if !guard.data.contains_key(&coord) {
-                 let dummy_path = vec![AffineTuple::identity(&guard.discriminant); guard.dimensions];
-                 return Ok(HtpResponse::ProofBundle {
-                    request_id: header.request_id,
-                    primary_path: dummy_path,
-                    orthogonal_anchors: vec![],
-                    epoch: 1,
-                });
+            match guard.root_at(epoch) {
+                Some(record) => Ok(HtpResponse::RootAt(record.clone())),
+                None => Ok(HtpResponse::Error(format!(
+                    "Epoch {} unavailable (current {}, history retains {} epochs).",
+                    epoch, guard.epoch, guard.history.len()
+                ))),
             }
+        },
 
-            let path = guard.get_segment_tree_path(&coord, 0); 
-            let anchors = guard.get_orthogonal_anchors(&coord, 0);
-            
-            Ok(HtpResponse::ProofBundle {
-                request_id: header.request_id,
-                primary_path: path,
-                orthogonal_anchors: anchors,
-                epoch: 1,
+        HtpRequest::GetUpdates { header, since_epoch } => {
+            validate_header(&header)?;
+            let guard = tensor.read().await;
+            Ok(HtpResponse::Updates {
+                discriminant: guard.discriminant.clone(),
+                updates: guard.updates_since(since_epoch)?,
             })
         },
-        
-        HtpRequest::GetGlobalRoot { header } => {
+
+        HtpRequest::GetConsistencyProof { header, from, to } => {
             validate_header(&header)?;
             let guard = tensor.read().await;
-            let root = match &guard.cached_root {
-                Some(r) => r.clone(),
-                None => guard.compute_root_internal()?
+            let (from_root, to_root) = match (guard.root_at(from), guard.root_at(to)) {
+                (Some(f), Some(t)) => (f.clone(), t.clone()),
+                _ => return Ok(HtpResponse::Error(format!("Epoch range {} -> {} unavailable.", from, to))),
             };
-            Ok(HtpResponse::GlobalRoot(root))
+            let proof = guard.consistency_proof(from, to)?;
+            Ok(HtpResponse::Consistency { from_root, to_root, proof, discriminant: guard.discriminant.clone() })
         },
 
-        HtpRequest::RegisterUser { header, user_id } => {
+        HtpRequest::RegisterUser { header, user_id, reregister } => {
             validate_header(&header)?;
             // [SECURITY FIX]: 防止日志伪造 (Log Injection)，转义用户输入
             info!("📝 Registering User '{}'", user_id.escape_debug());
 
-            let mut guard = tensor.write().await;
-            let p = crate::core::primes::hash_to_prime(&user_id, 64).map_err(|e| e.to_string())?;
-            let q_gen = crate::core::algebra::ClassGroupElement::generator(&guard.discriminant);
-            let tuple = AffineTuple { p_factor: p, q_shift: q_gen };
+            let prime_config = {
+                let guard = tensor.read().await;
+                // 幂等快速路径：已注册时无需再做 Hash-to-Prime
+                if !reregister && guard.is_registered(&user_id)? {
+                    return Ok(HtpResponse::AlreadyRegistered { request_id: header.request_id, epoch: guard.epoch });
+                }
+                guard.prime_config.clone()
+            };
 
-            guard.insert(&user_id, tuple)?;
-            
-            // 简单的同步持久化 (生产环境应异步处理)
-            if let Err(e) = guard.save_to_disk("htp_tensor.db") {
-                error!("Save failed: {}", e);
-            }
+            // [PERF FIX]: Hash-to-Prime 在计算池中执行，且不再占用写锁
+            let id = user_id.clone();
+            let rep = ctx.compute.run(move || prime_config.hash_to_prime(&id)).await?;
+
+            let policy = if reregister { RegistrationPolicy::Reregister } else { RegistrationPolicy::RejectDuplicates };
+            let guard = tensor.clone().write_owned().await;
+            // [PERF FIX]: 插入与折叠 (commit_epoch / freeze) 同样移入计算池，写锁随任务一同转移
+            let (guard, sealed) = ctx.compute.run(move || {
+                let mut guard = guard;
+                let q_gen = crate::core::algebra::ClassGroupElement::generator(&guard.discriminant);
+                let tuple = AffineTuple { p_factor: rep.prime, q_shift: q_gen };
+                let inserted = transact(&mut guard, |t| {
+                    if let InsertOutcome::AlreadyRegistered { .. } = t.insert_with_policy(&user_id, tuple, rep.nonce, policy)? {
+                        return Ok(false);
+                    }
+                    // 每次注册即一个批次，提交后根进入不可变历史
+                    t.commit_epoch()?;
+                    Ok(true)
+                })?;
+                if !inserted {
+                    return Ok((guard, None));
+                }
+                let index = guard.sequential_index(&user_id);
+                let sealed = seal(&mut guard);
+                Ok((guard, Some((sealed, index))))
+            }).await?;
+            let Some((sealed, index)) = sealed else {
+                return Ok(HtpResponse::AlreadyRegistered { request_id: header.request_id, epoch: guard.epoch });
+            };
+            // [FIX]: 追加 WAL 而非整库重写，崩溃不会损坏已有数据
+            let (receipt, epoch) = (persist(ctx, sealed), guard.epoch);
+            drop(guard);
+            // [PERF FIX]: 释放写锁后再等待组提交落盘；只有持久化成功才确认注册
+            durable(receipt).await?;
 
             Ok(HtpResponse::RegisterSuccess { 
                 request_id: header.request_id, 
-                epoch: 1 
+                epoch,
+                index,
             })
         }
+
+        HtpRequest::RegisterBatch { header, user_ids } => {
+            validate_header(&header)?;
+            info!("📦 Registering batch of {} users", user_ids.len());
+            if user_ids.len() > MAX_BATCH_SIZE {
+                return Err(format!("Batch too large: {} users (max {}).", user_ids.len(), MAX_BATCH_SIZE));
+            }
+
+            let (prime_config, pending) = {
+                let guard = tensor.read().await;
+                let pending: Vec<String> = guard.unregistered(&user_ids)?.into_iter().map(str::to_string).collect();
+                (guard.prime_config.clone(), pending)
+            };
+            // [PERF FIX]: 与单个注册相同，Hash-to-Prime 在计算池中执行且不持有任何锁；写锁只覆盖合并与提交
+            let primes = ctx.compute.run(move || batch_primes(&prime_config, &pending)).await?;
+
+            let guard = tensor.clone().write_owned().await;
+            let (guard, outcomes, sealed) = ctx.compute.run(move || {
+                let mut guard = guard;
+                let (outcomes, _record) = transact(&mut guard, |t| t.insert_batch_with(&user_ids, &primes))?;
+                let sealed = seal(&mut guard);
+                Ok((guard, outcomes, sealed))
+            }).await?;
+            // 整批只追加一次 WAL
+            let (receipt, epoch) = (persist(ctx, sealed), guard.epoch);
+            drop(guard);
+            durable(receipt).await?;
+            let registered = outcomes.iter().filter(|o| matches!(o, InsertOutcome::Inserted { .. })).count() as u32;
+
+            Ok(HtpResponse::BatchRegistered {
+                request_id: header.request_id,
+                epoch,
+                registered,
+                already_registered: outcomes.len() as u32 - registered,
+            })
+        },
+
+        HtpRequest::RevokeUser { header, user_id, operator_tag } => {
+            validate_header(&header)?;
+            // [SECURITY FIX]: 撤销为破坏性操作，必须携带运维 MAC；未配置运维口令的节点一律拒绝
+            // [SECURITY FIX]: 同一标签在时间窗口内只能使用一次
+            if let Some(reason) = authorize_operator(ctx, &header, |op, deployment| op.verify(deployment, &header, &user_id, &operator_tag))? {
+                warn!("🚫 Rejected revocation attempt for '{}': {}", user_id.escape_debug(), reason);
+                return Ok(HtpResponse::Error(reason.to_string()));
+            }
+            info!("🗑️ Revoking User '{}'", user_id.escape_debug());
+
+            let guard = tensor.clone().write_owned().await;
+            let (guard, sealed) = ctx.compute.run(move || {
+                let mut guard = guard;
+                let revoked = transact(&mut guard, |t| {
+                    if !t.revoke(&user_id)? {
+                        return Ok(false);
+                    }
+                    t.commit_epoch()?;
+                    Ok(true)
+                })?;
+                if !revoked {
+                    return Ok((guard, None));
+                }
+                let sealed = seal(&mut guard);
+                Ok((guard, Some(sealed)))
+            }).await?;
+            let Some(sealed) = sealed else {
+                return Ok(HtpResponse::Error("User not registered.".to_string()));
+            };
+            let (receipt, epoch) = (persist(ctx, sealed), guard.epoch);
+            drop(guard);
+            durable(receipt).await?;
+
+            Ok(HtpResponse::RevokeSuccess {
+                request_id: header.request_id,
+                epoch,
+            })
+        }
+
+        HtpRequest::Reshard { header, dimensions, side_length, operator_tag } => {
+            validate_header(&header)?;
+            if let Some(reason) = authorize_operator(ctx, &header, |op, deployment| op.verify_reshard(deployment, &header, dimensions, side_length, &operator_tag))? {
+                warn!("🚫 Rejected re-sharding attempt: {}", reason);
+                return Ok(HtpResponse::Error(reason.to_string()));
+            }
+            let (dim, len) = (dimensions as usize, side_length as usize);
+            if let Err(e) = HyperTensor::check_geometry(dim, len) {
+                return Ok(HtpResponse::Error(e));
+            }
+            info!("🔀 Re-sharding to {}^{}", len, dim);
+
+            let epoch = match reshard(tensor, ctx, dim, len).await {
+                Ok(epoch) => epoch,
+                Err(e) => {
+                    tensor.write().await.abort_reshard();
+                    return Err(e);
+                },
+            };
+            info!("✅ Re-sharded to {}^{} at epoch {}", len, dim, epoch);
+            Ok(HtpResponse::Resharded { request_id: header.request_id, epoch, dimensions, side_length })
+        }
+    }
+}
+
+/// [NEW FEATURE]: 在线重分片
+/// 重建期间旧张量继续服务证明与注册；切换在写锁内完成，新几何以新 epoch 生效
+async fn reshard(tensor: &Arc<RwLock<HyperTensor>>, ctx: &ServiceContext, dim: usize, len: usize) -> Result<u64, String> {
+    let frozen = tensor.write().await.begin_reshard()?;
+    let tag = format!("e{}", frozen.epoch);
+    let next = ctx.compute.run(move || {
+        let store = frozen.data.empty_sibling(&tag)?;
+        frozen.reshard_into(dim, len, store)
+    }).await?;
+
+    let rebuild: Rebuild = Box::new(move |live: &mut HyperTensor| {
+        let mut next = next;
+        let ops = live.reshard_tap.take().ok_or("Re-sharding was aborted.")?;
+        next.replay_remapped(ops)?;
+        next.complete_reshard(live)?;
+        Ok(next)
+    });
+    match &ctx.committer {
+        Some(committer) => committer.replace(rebuild).await,
+        None => {
+            let guard = tensor.clone().write_owned().await;
+            let (mut guard, mut next) = ctx.compute.run(move || {
+                let mut guard = guard;
+                let next = rebuild(&mut guard)?;
+                Ok((guard, next))
+            }).await?;
+            ctx.snapshots.publish(&mut next)?;
+            *guard = next;
+            Ok(guard.epoch)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::wire::PROTOCOL_VERSION;
+    use crate::core::algebra::ClassGroupElement;
+    use crate::topology::placement::{AddressingMode, FullCellPolicy, Placement};
+    use crate::topology::tensor::index_to_coord;
+    use crate::topology::testing::{discriminant, register};
+    use rug::Integer;
+
+    fn header(request_id: u64) -> RequestHeader {
+        RequestHeader { version: PROTOCOL_VERSION, timestamp: unix_now(), request_id }
+    }
+
+    #[test]
+    fn operator_request_ids_are_single_use() {
+        let cache = ReplayCache::new();
+        assert!(cache.admit(&header(7)));
+        assert!(!cache.admit(&header(7)));
+        assert!(cache.admit(&header(8)));
+    }
+
+    #[test]
+    fn operator_tags_are_bound_to_the_deployment() {
+        let op = OperatorAuth::from_secret("secret");
+        let header = header(1);
+        let tag = op.sign(&[1u8; 32], &header, "alice");
+        assert!(op.verify(&[1u8; 32], &header, "alice", &tag));
+        assert!(!op.verify(&[2u8; 32], &header, "alice", &tag));
+        assert!(!op.verify_reshard(&[1u8; 32], &header, 4, 16, &tag));
+    }
+
+    #[test]
+    fn failed_transactions_roll_back_to_the_last_commit() {
+        let mut tensor = HyperTensor::new(3, 5, discriminant());
+        tensor.addressing = AddressingMode::Sequential;
+        transact(&mut tensor, |t| {
+            register(t, "alice")?;
+            register(t, "bob")?;
+            t.commit_epoch()
+        }).unwrap();
+        tensor.take_wal_ops();
+        let root = tensor.calculate_global_root().unwrap();
+        let (epoch, next_index, alice) = (tensor.epoch, tensor.next_index, tensor.sequential_index("alice"));
+
+        let failed = transact(&mut tensor, |t| {
+            register(t, "carol")?;
+            t.revoke("alice")?;
+            Err::<(), _>("disk full".to_string())
+        });
+        assert!(failed.is_err());
+        assert!(tensor.is_registered("alice").unwrap());
+        assert!(!tensor.is_registered("carol").unwrap());
+        assert_eq!(tensor.sequential_index("alice"), alice);
+        assert_eq!((tensor.epoch, tensor.next_index, tensor.pending_changes), (epoch, next_index, 0));
+        assert!(tensor.wal_buffer.is_empty());
+        let rolled_back = tensor.calculate_global_root().unwrap();
+        assert!(rolled_back.p_factor == root.p_factor && rolled_back.q_shift == root.q_shift);
+
+        transact(&mut tensor, |t| {
+            register(t, "carol")?;
+            t.commit_epoch()
+        }).unwrap();
+        assert_eq!((tensor.epoch, tensor.sequential_index("carol")), (epoch + 1, Some(next_index)));
+    }
+
+    #[test]
+    fn failed_insert_records_no_address() {
+        let mut tensor = HyperTensor::new(1, 2, discriminant());
+        register(&mut tensor, "alice").unwrap();
+        let cell = tensor.map_id_to_coord_hash("alice");
+        let mallory = (0..).map(|i| format!("mallory-{}", i))
+            .find(|id| tensor.map_id_to_coord_hash(id) == cell)
+            .unwrap();
+        let ops = tensor.wal_buffer.len();
+
+        // P 超过熔断阈值，与 alice 合成时失败
+        let oversized = AffineTuple { p_factor: Integer::from(1) << 5000, q_shift: ClassGroupElement::generator(&tensor.discriminant) };
+        assert!(tensor.insert(&mallory, oversized, 0).is_err());
+        assert!(!tensor.addresses.contains_key(&crate::topology::membership::member_digest(&mallory)));
+        assert_eq!(tensor.wal_buffer.len(), ops);
+    }
+
+    #[test]
+    fn dummy_proofs_share_the_index_range_of_real_proofs() {
+        let mut tensor = HyperTensor::new(2, 16, discriminant());
+        tensor.addressing = AddressingMode::Sequential;
+        tensor.placement = Placement::keyed(0, FullCellPolicy::Reject).unwrap();
+        for i in 0..8 {
+            register(&mut tensor, &format!("user-{}", i)).unwrap();
+        }
+        tensor.commit_epoch().unwrap().unwrap();
+        let proof = |tensor: &HyperTensor, user_id: &str| match build_proof(tensor, user_id).unwrap() {
+            HtpResponse::ProofBundle { index, witness, .. } => (index.unwrap(), witness.coord),
+            other => panic!("unexpected response {:?}", other),
+        };
+
+        // 真实与假证明的序号都落在 0..next_index 内，坐标均由序号复算
+        let mut dummies = std::collections::BTreeSet::new();
+        for user_id in (0..8).map(|i| format!("user-{}", i)).chain((0..64).map(|i| format!("stranger-{}", i))) {
+            let (index, coord) = proof(&tensor, &user_id);
+            assert!(index < tensor.next_index, "{} got index {}", user_id, index);
+            assert_eq!(coord, index_to_coord(index, 2, 16), "{}", user_id);
+            if user_id.starts_with("stranger") {
+                assert!(!tensor.is_registered(&user_id).unwrap());
+                dummies.insert(index);
+            }
+        }
+        assert!(dummies.len() > 1);
+
+        // 假序号由部署密钥决定：换密钥后同一批非成员得到不同的序号
+        let before: Vec<u64> = (0..64).map(|i| proof(&tensor, &format!("stranger-{}", i)).0).collect();
+        tensor.placement = Placement::keyed(0, FullCellPolicy::Reject).unwrap();
+        let after: Vec<u64> = (0..64).map(|i| proof(&tensor, &format!("stranger-{}", i)).0).collect();
+        assert_ne!(before, after);
     }
 }
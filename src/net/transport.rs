@@ -11,7 +11,7 @@ pub struct QuicTransport {
 }
 
 impl QuicTransport {
-    pub async fn bind_server(addr: SocketAddr, cert_path: &str, key_path: &str) -> Result<Self, Box<dyn Error>> {
+    pub async fn bind_server(addr: SocketAddr, cert_path: &str, key_path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
         let (cert, key) = Self::load_or_generate_certs(cert_path, key_path)?;
         
         let server_crypto = rustls::ServerConfig::builder()
@@ -33,7 +33,7 @@ impl QuicTransport {
         Ok(Self { endpoint })
     }
 
-    pub fn bind_client() -> Result<Self, Box<dyn Error>> {
+    pub fn bind_client() -> Result<Self, Box<dyn Error + Send + Sync>> {
         let mut endpoint = Endpoint::client("0.0.0.0:0".parse().unwrap())?;
         let client_crypto = rustls::ClientConfig::builder()
             .with_safe_defaults()
@@ -43,7 +43,7 @@ impl QuicTransport {
         Ok(Self { endpoint })
     }
     
-    fn load_or_generate_certs(cert_path: &str, key_path: &str) -> Result<(Vec<rustls::Certificate>, rustls::PrivateKey), Box<dyn Error>> {
+    fn load_or_generate_certs(cert_path: &str, key_path: &str) -> Result<(Vec<rustls::Certificate>, rustls::PrivateKey), Box<dyn Error + Send + Sync>> {
         let c_path = std::path::Path::new(cert_path);
         let k_path = std::path::Path::new(key_path);
 
@@ -1,9 +1,14 @@
 // COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
 
 use serde::{Serialize, Deserialize};
+use rug::Integer;
 use crate::core::affine::AffineTuple;
+use crate::core::primes::PrimeHashConfig;
+use crate::topology::epoch::EpochRecord;
+use crate::topology::consistency::ConsistencyProof;
+use crate::topology::witness::{EpochUpdate, MembershipWitness};
 
-pub const PROTOCOL_VERSION: u16 = 1;
+pub const PROTOCOL_VERSION: u16 = 4;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RequestHeader {
@@ -21,25 +26,162 @@ pub enum HtpRequest {
     GetGlobalRoot {
         header: RequestHeader,
     },
+    // [NEW]: 查询历史 epoch 的根
+    GetRootAt {
+        header: RequestHeader,
+        epoch: u64,
+    },
+    // [NEW]: 见证更新 - since_epoch 之后每个 epoch 的增量数据
+    GetUpdates {
+        header: RequestHeader,
+        since_epoch: u64,
+    },
+    // [NEW]: 两个 epoch 之间的一致性证明
+    GetConsistencyProof {
+        header: RequestHeader,
+        from: u64,
+        to: u64,
+    },
     // [NEW]: 支持网络写入/注册
     RegisterUser {
         header: RequestHeader,
         user_id: String,
-    }
+        // [NEW]: 显式选择重新注册语义；默认重复注册返回 AlreadyRegistered
+        reregister: bool,
+    },
+    // [NEW]: 批量注册 (批量上线)，整批作为一个 epoch 提交
+    RegisterBatch {
+        header: RequestHeader,
+        user_ids: Vec<String>,
+    },
+    // [NEW]: 成员撤销，需运维授权
+    RevokeUser {
+        header: RequestHeader,
+        user_id: String,
+        operator_tag: [u8; 32],
+    },
+    // [NEW]: 在线重分片到新几何，需运维授权
+    Reshard {
+        header: RequestHeader,
+        dimensions: u32,
+        side_length: u64,
+        operator_tag: [u8; 32],
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum HtpResponse {
     ProofBundle {
         request_id: u64,
+        // [FIX]: 自单元格值逐层向上直到全局根的子张量聚合值 (取自聚合索引)，末项即证明所依据的根
         primary_path: Vec<AffineTuple>,
-        orthogonal_anchors: Vec<AffineTuple>,
+        // [NEW]: 素数代表元的 nonce，客户端只需一次素性测试即可校验身份绑定
+        prime_nonce: u64,
+        // [NEW]: 桶内有序成员链 (含自身)，按序合成应得到 primary_path[0]
+        bucket_depth: u32,
+        bucket_chain: Vec<AffineTuple>,
+        // [NEW]: 可在本地随 GetUpdates 增量更新的成员见证
+        witness: MembershipWitness,
+        // 证明生成时所依据的已提交 epoch
         epoch: u64,
+        // [NEW]: 顺序寻址时的逻辑序号，验证方据此复算 witness.coord
+        index: Option<u64>,
+        // [FIX]: 节点使用的 Hash-to-Prime 参数，客户端无需在命令行重复配置
+        prime_config: PrimeHashConfig,
+        // [FIX]: 节点的类群判别式，验证方据此合成，无需从某个二次型反推
+        discriminant: Integer,
+    },
+    GlobalRoot {
+        root: AffineTuple,
+        prime_config: PrimeHashConfig,
+        discriminant: Integer,
+        // [SECURITY FIX]: 部署标识 (参数指纹)，运维 MAC 与之绑定，标签不能跨部署或跨几何重放
+        deployment: [u8; 32],
+    },
+    RootAt(EpochRecord),
+    Updates {
+        discriminant: Integer,
+        updates: Vec<EpochUpdate>,
+    },
+    Consistency {
+        from_root: EpochRecord,
+        to_root: EpochRecord,
+        proof: ConsistencyProof,
+        discriminant: Integer,
     },
-    GlobalRoot(AffineTuple),
     RegisterSuccess { 
         request_id: u64, 
-        epoch: u64 
+        epoch: u64,
+        // 顺序寻址时分配的逻辑序号
+        index: Option<u64>,
+    },
+    // [NEW]: 幂等注册 - 该 ID 已在张量中，状态未改变
+    AlreadyRegistered {
+        request_id: u64,
+        epoch: u64,
+    },
+    BatchRegistered {
+        request_id: u64,
+        epoch: u64,
+        registered: u32,
+        already_registered: u32,
+    },
+    RevokeSuccess {
+        request_id: u64,
+        epoch: u64,
+    },
+    // 新几何自该 epoch 起生效；之前的见证需重新获取
+    Resharded {
+        request_id: u64,
+        epoch: u64,
+        dimensions: u32,
+        side_length: u64,
     },
     Error(String),
 }
+
+/// 运维授权：由共享口令派生密钥，对 (部署标识, header, user_id) 计算 blake3 keyed MAC
+/// 标签绑定 request_id 与 timestamp，配合 Header 的时间窗口与节点的 request_id 去重防止重放；
+/// 部署标识防止共用口令的另一部署 (或重分片前的同一部署) 接受同一标签
+#[derive(Clone)]
+pub struct OperatorAuth {
+    key: [u8; 32],
+}
+
+impl OperatorAuth {
+    pub fn from_secret(secret: &str) -> Self {
+        OperatorAuth { key: blake3::derive_key("htp operator auth v1", secret.as_bytes()) }
+    }
+
+    pub fn sign(&self, deployment: &[u8; 32], header: &RequestHeader, user_id: &str) -> [u8; 32] {
+        self.mac(b"htp:revoke:v1", deployment, header, user_id.as_bytes())
+    }
+
+    pub fn verify(&self, deployment: &[u8; 32], header: &RequestHeader, user_id: &str, tag: &[u8; 32]) -> bool {
+        // blake3::Hash 的相等比较为常数时间
+        blake3::Hash::from(self.sign(deployment, header, user_id)) == blake3::Hash::from(*tag)
+    }
+
+    /// 重分片授权：标签绑定目标几何，且与撤销标签域分离
+    pub fn sign_reshard(&self, deployment: &[u8; 32], header: &RequestHeader, dimensions: u32, side_length: u64) -> [u8; 32] {
+        let mut subject = dimensions.to_le_bytes().to_vec();
+        subject.extend_from_slice(&side_length.to_le_bytes());
+        self.mac(b"htp:reshard:v1", deployment, header, &subject)
+    }
+
+    pub fn verify_reshard(&self, deployment: &[u8; 32], header: &RequestHeader, dimensions: u32, side_length: u64, tag: &[u8; 32]) -> bool {
+        blake3::Hash::from(self.sign_reshard(deployment, header, dimensions, side_length)) == blake3::Hash::from(*tag)
+    }
+
+    fn mac(&self, domain: &[u8], deployment: &[u8; 32], header: &RequestHeader, subject: &[u8]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        hasher.update(domain);
+        hasher.update(deployment);
+        hasher.update(&header.version.to_le_bytes());
+        hasher.update(&header.timestamp.to_le_bytes());
+        hasher.update(&header.request_id.to_le_bytes());
+        hasher.update(&(subject.len() as u64).to_le_bytes());
+        hasher.update(subject);
+        *hasher.finalize().as_bytes()
+    }
+}
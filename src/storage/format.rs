@@ -0,0 +1,282 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use crate::core::primes::PrimeHashConfig;
+use crate::topology::placement::AddressingMode;
+use crate::topology::tensor::{Coordinate, HyperTensor};
+use super::store::CellRecord;
+use rug::Integer;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// 数据库文件魔数
+pub const DB_MAGIC: [u8; 8] = *b"HTPTNSR\0";
+/// 当前文件格式版本；HyperTensor 的序列化布局变化时递增，并在 decode_payload 中增加迁移分支
+/// v1: 文件头 + HyperTensor 的 bincode 载荷
+pub const DB_FORMAT_VERSION: u16 = 1;
+/// 无文件头的原始 bincode 布局 (格式 v0)
+pub const LEGACY_FORMAT_VERSION: u16 = 0;
+/// 原始节点 Hash-to-Prime 的素数位长
+const LEGACY_PRIME_BITS: u32 = 64;
+
+/// magic(8) | version(2) | param_hash(32) | epoch(8) | root_digest(32) | payload_len(8) | checksum(32)
+pub const HEADER_LEN: usize = 8 + 2 + 32 + 8 + 32 + 8 + 32;
+
+/// 自描述文件头：无需解码载荷即可确认参数、epoch 与完整性
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbHeader {
+    pub version: u16,
+    /// 系统参数指纹 (判别式、几何、Hash-to-Prime 配置)
+    pub param_hash: [u8; 32],
+    pub epoch: u64,
+    /// 最新已提交根的摘要
+    pub root_digest: [u8; 32],
+    pub payload_len: u64,
+    /// 载荷的 blake3 校验和
+    pub checksum: [u8; 32],
+}
+
+impl DbHeader {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN);
+        out.extend_from_slice(&DB_MAGIC);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&self.param_hash);
+        out.extend_from_slice(&self.epoch.to_le_bytes());
+        out.extend_from_slice(&self.root_digest);
+        out.extend_from_slice(&self.payload_len.to_le_bytes());
+        out.extend_from_slice(&self.checksum);
+        out
+    }
+
+    /// 不以魔数开头时返回 Ok(None) (旧版原始布局)
+    pub fn decode(bytes: &[u8]) -> Result<Option<Self>, String> {
+        if bytes.len() < DB_MAGIC.len() || bytes[..8] != DB_MAGIC {
+            return Ok(None);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err("Database header truncated.".to_string());
+        }
+        let array32 = |at: usize| -> [u8; 32] { bytes[at..at + 32].try_into().unwrap() };
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        Ok(Some(DbHeader {
+            version: u16::from_le_bytes([bytes[8], bytes[9]]),
+            param_hash: array32(10),
+            epoch: u64_at(42),
+            root_digest: array32(50),
+            payload_len: u64_at(82),
+            checksum: array32(90),
+        }))
+    }
+}
+
+pub fn param_hash(tensor: &HyperTensor) -> Result<[u8; 32], String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"htp:db:params:v1");
+    hasher.update(&(tensor.dimensions as u64).to_le_bytes());
+    hasher.update(&(tensor.side_length as u64).to_le_bytes());
+    let disc = tensor.discriminant.to_string_radix(16);
+    hasher.update(&(disc.len() as u64).to_le_bytes());
+    hasher.update(disc.as_bytes());
+    hasher.update(&bincode::serialize(&tensor.prime_config).map_err(|e| e.to_string())?);
+    // 只在启用时计入，未加密钥的数据库指纹保持不变
+    if let Some(key) = &tensor.placement.coord_key {
+        hasher.update(b"coord-key");
+        hasher.update(key);
+    }
+    if tensor.addressing != AddressingMode::Hashed {
+        hasher.update(b"addressing");
+        hasher.update(tensor.addressing.name().as_bytes());
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
+
+pub fn root_digest(root: Option<&AffineTuple>) -> Result<[u8; 32], String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"htp:db:root:v1");
+    if let Some(r) = root {
+        hasher.update(&bincode::serialize(r).map_err(|e| e.to_string())?);
+    }
+    Ok(*hasher.finalize().as_bytes())
+}
+
+fn committed_root(tensor: &HyperTensor) -> Option<&AffineTuple> {
+    tensor.history.back().map(|r| &r.root)
+}
+
+/// 文件头 + bincode 载荷
+pub fn encode_tensor(tensor: &HyperTensor) -> Result<Vec<u8>, String> {
+    let payload = bincode::serialize(tensor).map_err(|e| e.to_string())?;
+    let header = DbHeader {
+        version: DB_FORMAT_VERSION,
+        param_hash: param_hash(tensor)?,
+        epoch: tensor.epoch,
+        root_digest: root_digest(committed_root(tensor))?,
+        payload_len: payload.len() as u64,
+        checksum: *blake3::hash(&payload).as_bytes(),
+    };
+    let mut out = header.encode();
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// 解码数据库文件，返回张量与其来源格式版本 (LEGACY_FORMAT_VERSION 表示已从原始布局迁移)
+pub fn decode_tensor(bytes: &[u8]) -> Result<(HyperTensor, u16), String> {
+    let header = match DbHeader::decode(bytes)? {
+        Some(h) => h,
+        None => {
+            // [MIGRATION]: v0 - 无文件头的原始 bincode 布局
+            let tensor = decode_payload(LEGACY_FORMAT_VERSION, bytes)?;
+            return Ok((tensor, LEGACY_FORMAT_VERSION));
+        }
+    };
+
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() as u64 != header.payload_len {
+        return Err(format!("Database payload truncated: {} of {} bytes.", payload.len(), header.payload_len));
+    }
+    if *blake3::hash(payload).as_bytes() != header.checksum {
+        return Err("Database checksum mismatch: file is corrupted.".to_string());
+    }
+
+    let tensor = decode_payload(header.version, payload)?;
+    if param_hash(&tensor)? != header.param_hash {
+        return Err("Database parameter fingerprint mismatch.".to_string());
+    }
+    if tensor.epoch != header.epoch || root_digest(committed_root(&tensor))? != header.root_digest {
+        return Err("Database header does not match its payload (epoch/root).".to_string());
+    }
+    Ok((tensor, header.version))
+}
+
+/// 按格式版本解码载荷；新增版本时在此添加迁移分支
+fn decode_payload(version: u16, payload: &[u8]) -> Result<HyperTensor, String> {
+    let decode_error = |e: bincode::Error| format!("Failed to decode tensor (format v{}): {}", version, e);
+    match version {
+        // [MIGRATION]: v0 - 原始 HyperTensor：以坐标向量为键的单元格值，没有成员列表与 epoch
+        LEGACY_FORMAT_VERSION => {
+            let legacy: LegacyTensor = bincode::deserialize(payload).map_err(decode_error)?;
+            legacy.into_tensor()
+        },
+        DB_FORMAT_VERSION => bincode::deserialize(payload).map_err(decode_error),
+        other => Err(format!("Unsupported database format v{} (this build reads up to v{}).", other, DB_FORMAT_VERSION)),
+    }
+}
+
+/// 只读取文件头 (不解码载荷)；旧版原始布局返回 None
+pub fn read_header(path: &std::path::Path) -> Result<Option<DbHeader>, String> {
+    use std::io::Read;
+    let mut buf = vec![0u8; HEADER_LEN];
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let n = file.read(&mut buf).map_err(|e| e.to_string())?;
+    DbHeader::decode(&buf[..n])
+}
+
+/// v0 的序列化布局 (字段顺序必须与原始 HyperTensor 一致；cached_root 未序列化)
+#[derive(Deserialize)]
+struct LegacyTensor {
+    dimensions: usize,
+    side_length: usize,
+    discriminant: Integer,
+    data: HashMap<Coordinate, AffineTuple>,
+}
+
+impl LegacyTensor {
+    /// 单元格值原样保留 (成员列表未知，记为空)；原始节点以 64-bit 旧版派生生成素数、以公开映射寻址。
+    /// 迁移后的内容作为 epoch 0 的创世状态。
+    fn into_tensor(self) -> Result<HyperTensor, String> {
+        if HyperTensor::cell_capacity(self.dimensions, self.side_length).is_none() {
+            return Err(format!("Legacy tensor geometry {}^{} exceeds the 128-bit coordinate space.", self.side_length, self.dimensions));
+        }
+        let mut tensor = HyperTensor::new_with_config(
+            self.dimensions,
+            self.side_length,
+            self.discriminant,
+            PrimeHashConfig::legacy(LEGACY_PRIME_BITS),
+        );
+        for (coord, value) in self.data {
+            if coord.len() != tensor.dimensions || coord.iter().any(|&c| c >= tensor.side_length) {
+                return Err(format!("Legacy tensor holds a cell outside its {}^{} geometry: {:?}.", tensor.side_length, tensor.dimensions, coord));
+            }
+            let key = tensor.pack(&coord);
+            tensor.data.put(key, CellRecord { value, members: Vec::new() })?;
+        }
+        tensor.rebuild_aggregates();
+        let root = tensor.calculate_global_root()?;
+        if let Some(genesis) = tensor.history.back_mut() {
+            genesis.root = root;
+        }
+        Ok(tensor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::algebra::ClassGroupElement;
+    use serde::Serialize;
+
+    /// 原始 HyperTensor 的序列化布局 (写入端)
+    #[derive(Serialize)]
+    struct BaselineTensor {
+        dimensions: usize,
+        side_length: usize,
+        discriminant: Integer,
+        data: HashMap<Coordinate, AffineTuple>,
+    }
+
+    fn baseline() -> BaselineTensor {
+        let discriminant = crate::topology::testing::discriminant();
+        let g = ClassGroupElement::generator(&discriminant);
+        let mut data = HashMap::new();
+        for (i, coord) in [vec![0, 1, 2], vec![3, 3, 3], vec![2, 0, 1]].into_iter().enumerate() {
+            let q_shift = g.pow(&Integer::from(i + 2), &discriminant).unwrap();
+            data.insert(coord, AffineTuple { p_factor: Integer::from(1_000_003 + 2 * i), q_shift });
+        }
+        BaselineTensor { dimensions: 3, side_length: 4, discriminant, data }
+    }
+
+    #[test]
+    fn baseline_file_round_trip() {
+        let original = baseline();
+        let path = std::env::temp_dir().join(format!("htp-baseline-{}.db", std::process::id()));
+        std::fs::write(&path, bincode::serialize(&original).unwrap()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let (mut migrated, version) = decode_tensor(&bytes).unwrap();
+        assert_eq!(version, LEGACY_FORMAT_VERSION);
+        assert_eq!((migrated.dimensions, migrated.side_length), (3, 4));
+        assert_eq!(migrated.discriminant, original.discriminant);
+        assert_eq!(migrated.prime_config, PrimeHashConfig::legacy(LEGACY_PRIME_BITS));
+        assert_eq!(migrated.data.len(), original.data.len());
+        for (coord, value) in &original.data {
+            let cell = migrated.data.get(migrated.pack(coord)).unwrap().unwrap();
+            assert_eq!(cell.value.p_factor, value.p_factor);
+            assert_eq!(cell.value.q_shift, value.q_shift);
+            assert!(cell.members.is_empty());
+        }
+        let root = migrated.calculate_global_root().unwrap();
+        assert_eq!(migrated.epoch, 0);
+        assert_eq!(migrated.root_at(0).unwrap().root.p_factor, root.p_factor);
+
+        // 以当前格式重写后原样读回
+        let (mut reloaded, version) = decode_tensor(&encode_tensor(&migrated).unwrap()).unwrap();
+        assert_eq!(version, DB_FORMAT_VERSION);
+        reloaded.rebuild_aggregates();
+        let again = reloaded.calculate_global_root().unwrap();
+        assert_eq!(again.p_factor, root.p_factor);
+        assert_eq!(again.q_shift, root.q_shift);
+    }
+
+    #[test]
+    fn baseline_cells_outside_the_geometry_are_rejected() {
+        let mut original = baseline();
+        original.data.insert(vec![4, 0, 0], original.data.values().next().unwrap().clone());
+        assert!(decode_tensor(&bincode::serialize(&original).unwrap()).is_err());
+
+        let mut original = baseline();
+        original.data.insert(vec![1, 1], original.data.values().next().unwrap().clone());
+        assert!(decode_tensor(&bincode::serialize(&original).unwrap()).is_err());
+    }
+}
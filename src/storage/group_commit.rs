@@ -0,0 +1,210 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use super::Persistence;
+use super::wal::WalOp;
+use crate::topology::snapshot::EpochSnapshots;
+use crate::topology::tensor::HyperTensor;
+use log::{error, warn};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::time::Instant;
+
+/// 组提交默认最长等待 (首个请求入队后)
+pub const DEFAULT_COMMIT_DELAY: Duration = Duration::from_millis(2);
+/// 组提交默认最多合并的请求数
+pub const DEFAULT_COMMIT_BATCH: usize = 256;
+
+#[derive(Clone, Copy, Debug)]
+pub struct GroupCommitConfig {
+    /// 首个请求入队后最多再等待多久凑批
+    pub max_delay: Duration,
+    /// 达到该请求数立即落盘
+    pub max_batch: usize,
+}
+
+impl Default for GroupCommitConfig {
+    fn default() -> Self {
+        GroupCommitConfig { max_delay: DEFAULT_COMMIT_DELAY, max_batch: DEFAULT_COMMIT_BATCH }
+    }
+}
+
+/// 落盘回执：持久化任务追加并 fsync 后发送
+pub type Receipt = oneshot::Receiver<Result<(), String>>;
+
+/// 以当前张量构造替换张量 (如重分片切换)；在持久化任务中持有写锁执行
+pub type Rebuild = Box<dyn FnOnce(&mut HyperTensor) -> Result<HyperTensor, String> + Send>;
+
+enum Command {
+    Commit(Box<CommitRequest>),
+    Replace(Replacement),
+}
+
+struct Replacement {
+    rebuild: Rebuild,
+    done: oneshot::Sender<Result<u64, String>>,
+}
+
+struct CommitRequest {
+    ops: Vec<WalOp>,
+    /// 本次提交后的冻结快照，落盘后才发布
+    frozen: Option<HyperTensor>,
+    done: oneshot::Sender<Result<(), String>>,
+}
+
+/// [NEW FEATURE]: 后台持久化任务 + 组提交
+/// 请求处理器在写锁内入队 (入队顺序即 epoch 顺序即 WAL 顺序)，释放锁后等待回执；
+/// 任务把一段时间窗口内的请求合并为一次追加与一次 fsync，成功后发布最新快照再逐个回执。
+/// 写快照时持有张量写锁并先清空队列，保证快照中的 wal_seq 覆盖张量内的全部变更。
+pub struct GroupCommitter {
+    tx: mpsc::UnboundedSender<Command>,
+}
+
+impl GroupCommitter {
+    pub fn spawn(
+        persistence: Persistence,
+        tensor: Arc<RwLock<HyperTensor>>,
+        snapshots: Arc<EpochSnapshots>,
+        config: GroupCommitConfig,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run(Arc::new(Mutex::new(persistence)), tensor, snapshots, config, rx));
+        GroupCommitter { tx }
+    }
+
+    /// 必须在持有张量写锁时调用
+    pub fn submit(&self, ops: Vec<WalOp>, frozen: Option<HyperTensor>) -> Result<Receipt, String> {
+        let (done, receipt) = oneshot::channel();
+        self.tx.send(Command::Commit(Box::new(CommitRequest { ops, frozen, done }))).map_err(|_| "Persistence task stopped.".to_string())?;
+        Ok(receipt)
+    }
+
+    /// 原子替换张量：排队的提交先落盘，新张量的快照写入成功后才替换并发布，返回新 epoch。
+    /// 快照即新的恢复基点 (WAL 同时清空)；失败时旧张量与日志保持不变。
+    /// 调用方不得持有张量锁。
+    pub async fn replace(&self, rebuild: Rebuild) -> Result<u64, String> {
+        let (done, receipt) = oneshot::channel();
+        self.tx.send(Command::Replace(Replacement { rebuild, done })).map_err(|_| "Persistence task stopped.".to_string())?;
+        receipt.await.map_err(|_| "Persistence task stopped.".to_string())?
+    }
+}
+
+async fn run(
+    persistence: Arc<Mutex<Persistence>>,
+    tensor: Arc<RwLock<HyperTensor>>,
+    snapshots: Arc<EpochSnapshots>,
+    config: GroupCommitConfig,
+    mut rx: mpsc::UnboundedReceiver<Command>,
+) {
+    while let Some(first) = rx.recv().await {
+        let mut batch = Vec::new();
+        let mut replacements = Vec::new();
+        match first {
+            Command::Commit(req) => batch.push(*req),
+            Command::Replace(r) => replacements.push(r),
+        }
+        if replacements.is_empty() {
+            let deadline = Instant::now() + config.max_delay;
+            while batch.len() < config.max_batch.max(1) {
+                match tokio::time::timeout_at(deadline, rx.recv()).await {
+                    Ok(Some(Command::Commit(req))) => batch.push(*req),
+                    Ok(Some(Command::Replace(r))) => {
+                        replacements.push(r);
+                        break;
+                    },
+                    _ => break,
+                }
+            }
+        }
+        flush(&persistence, &snapshots, batch).await;
+
+        let due = persistence.lock().map(|p| p.snapshot_due()).unwrap_or(false);
+        if due || !replacements.is_empty() {
+            exclusive(&persistence, &tensor, &snapshots, &mut rx, replacements).await;
+        }
+    }
+}
+
+/// 持有张量写锁：锁内张量的全部变更都已入队，先落盘，再执行替换并按需以最后的序号写快照
+async fn exclusive(
+    persistence: &Arc<Mutex<Persistence>>,
+    tensor: &Arc<RwLock<HyperTensor>>,
+    snapshots: &Arc<EpochSnapshots>,
+    rx: &mut mpsc::UnboundedReceiver<Command>,
+    mut replacements: Vec<Replacement>,
+) {
+    let mut guard = tensor.clone().write_owned().await;
+    let mut pending = Vec::new();
+    while let Ok(cmd) = rx.try_recv() {
+        match cmd {
+            Command::Commit(req) => pending.push(*req),
+            Command::Replace(r) => replacements.push(r),
+        }
+    }
+    flush(persistence, snapshots, pending).await;
+
+    let p = persistence.clone();
+    let snapshots = snapshots.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut p = p.lock().map_err(|_| "Persistence lock poisoned.".to_string())?;
+        for Replacement { rebuild, done } in replacements {
+            let _ = done.send(replace_with(&mut p, &mut guard, &snapshots, rebuild));
+        }
+        if p.snapshot_due() {
+            guard.wal_seq = p.last_seq();
+            p.snapshot(&mut guard)?;
+        }
+        Ok::<(), String>(())
+    }).await;
+    match result {
+        Ok(Ok(())) => {},
+        Ok(Err(e)) => warn!("⚠️  Snapshot failed (WAL keeps growing): {}", e),
+        Err(e) => warn!("⚠️  Snapshot task panicked: {}", e),
+    }
+}
+
+/// 新张量的快照落盘即切换完成 (成为新的恢复基点)，随后替换内存中的张量并发布
+fn replace_with(p: &mut Persistence, live: &mut HyperTensor, snapshots: &EpochSnapshots, rebuild: Rebuild) -> Result<u64, String> {
+    let mut next = rebuild(live)?;
+    next.wal_seq = p.last_seq();
+    p.snapshot(&mut next)?;
+    *live = next;
+    if let Err(e) = snapshots.publish(live) {
+        warn!("⚠️  Failed to publish the snapshot after replacement: {}", e);
+    }
+    Ok(live.epoch)
+}
+
+/// 一组请求只追加并 fsync 一次；成功后发布其中最新的快照，再回执全部请求；失败时停机
+async fn flush(persistence: &Arc<Mutex<Persistence>>, snapshots: &EpochSnapshots, batch: Vec<CommitRequest>) {
+    if batch.is_empty() {
+        return;
+    }
+    let mut ops = Vec::new();
+    let mut latest = None;
+    let mut waiters = Vec::with_capacity(batch.len());
+    for req in batch {
+        ops.extend(req.ops);
+        if req.frozen.is_some() {
+            latest = req.frozen;
+        }
+        waiters.push(req.done);
+    }
+
+    let p = persistence.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        p.lock().map_err(|_| "Persistence lock poisoned.".to_string())?.append(ops)
+    }).await.map_err(|e| e.to_string()).and_then(|r| r);
+
+    // [FIX]: 这些 epoch 已在内存中提交且无法撤回；追加失败即停机，重启后由 WAL 恢复到最后一次落盘的提交
+    if let Err(e) = result {
+        error!("💀 Group commit failed: {}. Stopping the node; restart recovers from the WAL.", e);
+        std::process::exit(1);
+    }
+    if let Some(t) = latest {
+        snapshots.install(t);
+    }
+    for done in waiters {
+        let _ = done.send(Ok(()));
+    }
+}
@@ -0,0 +1,244 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+pub mod format;
+pub mod group_commit;
+pub mod store;
+pub mod wal;
+#[cfg(test)]
+pub mod testing;
+
+use crate::topology::tensor::HyperTensor;
+use log::{info, warn};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use wal::{read_wal, WalEntry, WalOp, WriteAheadLog};
+
+/// 默认每累积多少条 WAL 记录写一次快照
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 10_000;
+
+/// 原子写文件：写入同目录临时文件 → fsync → rename → fsync 目录
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp).map_err(|e| e.to_string())?;
+        file.write_all(bytes).map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        // 目录 fsync 保证 rename 本身持久化 (部分平台不支持，忽略错误)
+        let _ = File::open(dir).and_then(|d| d.sync_all());
+    }
+    Ok(())
+}
+
+/// 快照 + 预写日志的崩溃安全持久化
+/// 每次提交先追加 WAL (fsync)，累积到阈值后原子写入快照并清空日志。
+/// 快照记录已包含的最后一条 WAL 序号，恢复时跳过重复记录，因此快照与清空日志之间崩溃也是安全的。
+pub struct Persistence {
+    snapshot_path: PathBuf,
+    wal: WriteAheadLog,
+    next_seq: u64,
+    since_snapshot: u64,
+    pub snapshot_interval: u64,
+}
+
+impl Persistence {
+    /// 恢复：加载快照 (若存在)，然后重放其后已提交的 WAL 记录
+    /// 返回 None 表示快照与日志均不存在 (全新节点)
+    pub fn open(snapshot_path: &Path, wal_path: &Path) -> Result<(Self, Option<HyperTensor>), String> {
+        let mut migrated = false;
+        let mut tensor = if snapshot_path.exists() {
+            let bytes = std::fs::read(snapshot_path).map_err(|e| e.to_string())?;
+            let (mut t, version) = format::decode_tensor(&bytes)?;
+            // 聚合索引不落盘，加载后按需重建
+            t.rebuild_aggregates();
+            if version != format::DB_FORMAT_VERSION {
+                warn!("⚠️  Migrating database from format v{} to v{}.", version, format::DB_FORMAT_VERSION);
+                migrated = true;
+            }
+            Some(t)
+        } else {
+            None
+        };
+
+        // 中段损坏时 read_wal 报错，节点拒绝启动
+        let mut entries = read_wal(wal_path)?;
+        let valid_len = entries.last().map_or(0, |(_, end)| *end);
+        // 只保留到最后一个 Commit 为止：撕裂的尾帧与未提交的记录都从未被确认，截断后续追加才不会与之混在一起
+        let committed = entries.iter().rposition(|(e, _)| matches!(e.op, WalOp::Commit { .. }));
+        let keep_len = committed.map_or(0, |i| entries[i].1);
+        let uncommitted = entries.len() - committed.map_or(0, |i| i + 1);
+        entries.truncate(committed.map_or(0, |i| i + 1));
+        let on_disk = std::fs::metadata(wal_path).map(|m| m.len()).unwrap_or(0);
+        if valid_len < on_disk {
+            warn!("⚠️  WAL ends with a torn frame ({} bytes past the last complete record).", on_disk - valid_len);
+        }
+        if uncommitted > 0 {
+            warn!("⚠️  WAL ends with {} uncommitted record(s); they were never acknowledged.", uncommitted);
+        }
+        if keep_len < on_disk {
+            warn!("⚠️  Truncating WAL to the last commit ({} of {} bytes).", keep_len, on_disk);
+            let file = OpenOptions::new().write(true).open(wal_path).map_err(|e| e.to_string())?;
+            file.set_len(keep_len).map_err(|e| e.to_string())?;
+            file.sync_all().map_err(|e| e.to_string())?;
+        }
+        let entries: Vec<WalEntry> = entries.into_iter().map(|(e, _)| e).collect();
+
+        let mut next_seq = tensor.as_ref().map_or(0, |t| t.wal_seq) + 1;
+        let mut since_snapshot = 0;
+        if let Some(t) = tensor.as_mut() {
+            let (replayed, last_seq) = replay(t, entries)?;
+            if replayed > 0 {
+                info!("🔁 Replayed {} WAL records past the snapshot.", replayed);
+            }
+            next_seq = next_seq.max(last_seq + 1);
+            since_snapshot = replayed;
+        } else if !entries.is_empty() {
+            return Err("WAL present without a base snapshot; refusing to start with unknown parameters.".to_string());
+        }
+
+        let mut persistence = Persistence {
+            snapshot_path: snapshot_path.to_path_buf(),
+            wal: WriteAheadLog::open(wal_path)?,
+            next_seq,
+            since_snapshot,
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+        };
+        // 迁移后立即以新格式重写快照
+        if let (true, Some(t)) = (migrated, tensor.as_mut()) {
+            persistence.snapshot(t)?;
+        }
+        Ok((persistence, tensor))
+    }
+
+    /// 将张量中尚未持久化的操作追加到 WAL；达到阈值时写快照
+    pub fn commit(&mut self, tensor: &mut HyperTensor) -> Result<(), String> {
+        self.append(tensor.take_wal_ops())?;
+        tensor.wal_seq = self.last_seq();
+        if self.snapshot_due() {
+            self.snapshot(tensor)?;
+        }
+        Ok(())
+    }
+
+    /// 分配序号并追加一组操作 (一次 fsync)
+    pub fn append(&mut self, ops: Vec<WalOp>) -> Result<(), String> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        // [FIX]: 追加成功后才推进序号，失败不会在日志中留下序号空洞
+        let entries: Vec<WalEntry> = ops.into_iter().zip(self.next_seq..)
+            .map(|(op, seq)| WalEntry { seq, op })
+            .collect();
+        self.wal.append(&entries)?;
+        self.next_seq += entries.len() as u64;
+        self.since_snapshot += entries.len() as u64;
+        Ok(())
+    }
+
+    /// 最后一条已追加记录的序号
+    pub fn last_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    /// 自上次快照以来的记录数已达到阈值
+    pub fn snapshot_due(&self) -> bool {
+        self.since_snapshot >= self.snapshot_interval
+    }
+
+    /// 原子写快照，然后清空日志
+    /// 磁盘存储后端先做压缩；被新一代日志取代的文件在快照落盘后才删除
+    pub fn snapshot(&mut self, tensor: &mut HyperTensor) -> Result<(), String> {
+        tensor.data.compact()?;
+        tensor.save_to_disk(&self.snapshot_path.to_string_lossy())?;
+        tensor.data.release_stale()?;
+        self.wal.reset()?;
+        self.since_snapshot = 0;
+        info!("💾 Snapshot written (epoch {}, WAL seq {}).", tensor.epoch, tensor.wal_seq);
+        Ok(())
+    }
+}
+
+/// 按序重放 seq 大于快照的记录 (调用方已截掉未提交的尾部)
+fn replay(tensor: &mut HyperTensor, entries: Vec<WalEntry>) -> Result<(u64, u64), String> {
+    let mut applied = 0u64;
+    let base = tensor.wal_seq;
+    let mut last_seq = base;
+    for entry in entries.into_iter().filter(|e| e.seq > base) {
+        last_seq = entry.seq;
+        tensor.apply_wal_op(entry.op)?;
+        applied += 1;
+    }
+    tensor.wal_seq = last_seq;
+    Ok((applied, last_seq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::testing::Scratch;
+    use crate::topology::testing::{discriminant, register};
+
+    /// 快照 + 两个已提交 epoch 的 WAL
+    fn committed_log(dir: &Path) -> (PathBuf, PathBuf) {
+        let (snapshot, wal) = (dir.join("htp.db"), dir.join("htp.wal"));
+        HyperTensor::new(2, 4, discriminant()).save_to_disk(&snapshot.to_string_lossy()).unwrap();
+        let (mut persistence, tensor) = Persistence::open(&snapshot, &wal).unwrap();
+        let mut tensor = tensor.unwrap();
+        for user_id in ["alice", "bob"] {
+            register(&mut tensor, user_id).unwrap();
+            tensor.commit_epoch().unwrap();
+            persistence.commit(&mut tensor).unwrap();
+        }
+        (snapshot, wal)
+    }
+
+    fn append_raw(path: &Path, bytes: &[u8]) {
+        OpenOptions::new().append(true).open(path).unwrap().write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn torn_final_frame_is_truncated() {
+        let scratch = Scratch::new("torn");
+        let (snapshot, wal) = committed_log(&scratch.0);
+        let committed_len = std::fs::metadata(&wal).unwrap().len();
+
+        let mut frame = Vec::new();
+        wal::encode_frame(&[7u8; 40], &mut frame);
+        append_raw(&wal, &frame[..frame.len() / 2]);
+
+        let (_, tensor) = Persistence::open(&snapshot, &wal).unwrap();
+        assert_eq!(tensor.unwrap().epoch, 2);
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), committed_len);
+    }
+
+    #[test]
+    fn uncommitted_tail_is_dropped() {
+        let scratch = Scratch::new("uncommitted");
+        let (snapshot, wal) = committed_log(&scratch.0);
+        let committed_len = std::fs::metadata(&wal).unwrap().len();
+
+        let entry = WalEntry { seq: 1_000, op: WalOp::Address { id_digest: [1u8; 32], address: 9 } };
+        WriteAheadLog::open(&wal).unwrap().append(&[entry]).unwrap();
+
+        let (_, tensor) = Persistence::open(&snapshot, &wal).unwrap();
+        assert_eq!(tensor.unwrap().epoch, 2);
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), committed_len);
+    }
+
+    #[test]
+    fn mid_log_corruption_refuses_to_start() {
+        let scratch = Scratch::new("corrupt");
+        let (snapshot, wal) = committed_log(&scratch.0);
+        let mut bytes = std::fs::read(&wal).unwrap();
+        bytes[wal::FRAME_HEADER + 1] ^= 0xff;
+        std::fs::write(&wal, &bytes).unwrap();
+
+        let err = Persistence::open(&snapshot, &wal).err().expect("corrupt WAL must be rejected");
+        assert!(err.contains("corrupt"), "{}", err);
+        // 已提交的记录原样保留，留给运维处理
+        assert_eq!(std::fs::read(&wal).unwrap(), bytes);
+    }
+}
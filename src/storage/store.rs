@@ -0,0 +1,638 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use crate::topology::membership::CellMember;
+use crate::topology::tensor::PackedCoord;
+use super::wal::{checksum, encode_frame, FRAME_HEADER, MAX_FRAME_LEN};
+use log::{info, warn};
+use serde::{de, ser, Serialize, Deserialize, Serializer, Deserializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::{Deref, DerefMut, Range};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// 内存后端的单元格上限 (防止 GMP OOM 导致进程 Abort)
+pub const MEMORY_CELL_LIMIT: usize = 10_000_000;
+/// 日志后端：文件超过该大小且死数据多于活数据时，在快照前压缩
+pub const COMPACTION_MIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// 单元格记录 - 存储后端的读写单位：聚合值 + 按 depth 排列的成员列表
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CellRecord {
+    pub value: AffineTuple,
+    pub members: Vec<CellMember>,
+}
+
+/// 内存后端的单元格映射：持久化有序映射 + 共享记录，冻结副本为 O(1) 结构共享
+pub type SharedCells = im::OrdMap<PackedCoord, Arc<CellRecord>>;
+
+/// 按坐标升序的单元格迭代器
+pub type CellIter<'a> = Box<dyn Iterator<Item = Result<(PackedCoord, Cow<'a, CellRecord>), String>> + 'a>;
+
+/// [NEW FEATURE]: 可插拔的单元格存储后端
+/// 按打包坐标有序存取；读取返回 Cow，内存后端零拷贝，磁盘后端按需解码。
+pub trait TensorStore: Send + Sync {
+    fn backend(&self) -> StoreBackend;
+
+    fn get(&self, key: PackedCoord) -> Result<Option<Cow<'_, CellRecord>>, String>;
+
+    fn put(&mut self, key: PackedCoord, cell: CellRecord) -> Result<(), String>;
+
+    fn remove(&mut self, key: PackedCoord) -> Result<(), String>;
+
+    fn contains(&self, key: PackedCoord) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 区间内的单元格，按坐标升序
+    fn range(&self, range: Range<PackedCoord>) -> CellIter<'_>;
+
+    /// 区间内已占用的坐标 (不读取单元格内容)
+    fn keys(&self, range: Range<PackedCoord>) -> Box<dyn Iterator<Item = PackedCoord> + '_>;
+
+    /// 单元格数上限；None 表示只受磁盘容量限制
+    fn capacity(&self) -> Option<usize>;
+
+    /// 写入数据库快照的描述：内存后端为单元格本身，日志后端为已 fsync 的日志位置
+    fn snapshot(&self) -> Result<StoreSnapshot<'_>, String>;
+
+    /// 快照前的维护 (如日志压缩)
+    fn compact(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// 快照已持久化，回收不再被引用的文件
+    fn release_stale(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// 当前内容的只读副本 (与活动存储结构共享，之后的写入对其不可见)
+    fn freeze(&self) -> CellStore;
+
+    /// 同一后端的空存储 (重分片时承载新几何)；tag 区分磁盘后端的目录
+    fn empty_sibling(&self, tag: &str) -> Result<CellStore, String>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StoreBackend {
+    /// 有序内存映射 (默认)
+    #[default]
+    Memory,
+    /// 磁盘日志结构存储，内存中只保留坐标索引
+    Log,
+}
+
+impl StoreBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StoreBackend::Memory => "memory",
+            StoreBackend::Log => "log",
+        }
+    }
+}
+
+impl fmt::Display for StoreBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "memory" | "mem" => Ok(StoreBackend::Memory),
+            "log" | "disk" => Ok(StoreBackend::Log),
+            other => Err(format!("Unknown store backend '{}'. Expected one of: memory, log.", other)),
+        }
+    }
+}
+
+/// 存储后端在数据库快照中的形式
+#[derive(Serialize, Deserialize)]
+pub enum StoreSnapshot<'a> {
+    Memory(Cow<'a, SharedCells>),
+    /// 日志前 len 字节即快照时刻的全部单元格；之后的写入由 WAL 重放
+    Log { dir: PathBuf, generation: u64, len: u64 },
+}
+
+/// HyperTensor 持有的存储句柄；序列化为 StoreSnapshot，反序列化时重新打开对应后端
+pub struct CellStore(Box<dyn TensorStore>);
+
+impl CellStore {
+    pub fn new<S: TensorStore + 'static>(store: S) -> Self {
+        CellStore(Box::new(store))
+    }
+}
+
+impl Default for CellStore {
+    fn default() -> Self {
+        CellStore::new(MemoryStore::default())
+    }
+}
+
+impl Deref for CellStore {
+    type Target = dyn TensorStore;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl DerefMut for CellStore {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut()
+    }
+}
+
+impl Serialize for CellStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.snapshot().map_err(ser::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CellStore {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match StoreSnapshot::deserialize(deserializer)? {
+            StoreSnapshot::Memory(cells) => Ok(CellStore::new(MemoryStore { cells: cells.into_owned() })),
+            StoreSnapshot::Log { dir, generation, len } => {
+                LogStore::open_at(&dir, generation, len).map(CellStore::new).map_err(de::Error::custom)
+            },
+        }
+    }
+}
+
+/// 内存后端：有序映射
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    cells: SharedCells,
+}
+
+impl TensorStore for MemoryStore {
+    fn backend(&self) -> StoreBackend {
+        StoreBackend::Memory
+    }
+
+    fn get(&self, key: PackedCoord) -> Result<Option<Cow<'_, CellRecord>>, String> {
+        Ok(self.cells.get(&key).map(|c| Cow::Borrowed(c.as_ref())))
+    }
+
+    fn put(&mut self, key: PackedCoord, cell: CellRecord) -> Result<(), String> {
+        self.cells.insert(key, Arc::new(cell));
+        Ok(())
+    }
+
+    fn remove(&mut self, key: PackedCoord) -> Result<(), String> {
+        self.cells.remove(&key);
+        Ok(())
+    }
+
+    fn contains(&self, key: PackedCoord) -> bool {
+        self.cells.contains_key(&key)
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn range(&self, range: Range<PackedCoord>) -> CellIter<'_> {
+        Box::new(self.cells.range(range).map(|(k, c)| Ok((*k, Cow::Borrowed(c.as_ref())))))
+    }
+
+    fn keys(&self, range: Range<PackedCoord>) -> Box<dyn Iterator<Item = PackedCoord> + '_> {
+        Box::new(self.cells.range(range).map(|(k, _)| *k))
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(MEMORY_CELL_LIMIT)
+    }
+
+    fn snapshot(&self) -> Result<StoreSnapshot<'_>, String> {
+        Ok(StoreSnapshot::Memory(Cow::Borrowed(&self.cells)))
+    }
+
+    fn freeze(&self) -> CellStore {
+        CellStore::new(self.clone())
+    }
+
+    fn empty_sibling(&self, _tag: &str) -> Result<CellStore, String> {
+        Ok(CellStore::default())
+    }
+}
+
+/// 日志中的一条记录；cell 为 None 表示删除 (墓碑)
+#[derive(Serialize, Deserialize)]
+struct LogRecord {
+    key: PackedCoord,
+    cell: Option<CellRecord>,
+}
+
+/// 只解码记录头 (坐标 + Option 标签)，扫描重建索引时不必解码大整数
+#[derive(Deserialize)]
+struct LogRecordHead {
+    key: PackedCoord,
+    present: u8,
+}
+
+/// 坐标 → (帧起始偏移, 载荷长度)
+type LogIndex = im::OrdMap<PackedCoord, (u64, u32)>;
+
+/// 磁盘日志结构后端 (Bitcask 式)
+/// 单元格记录以校验帧追加到 `cells-<generation>.log`，内存中只保留 坐标 → 帧位置 的索引，
+/// 因此可服务大于内存的张量。覆盖写与删除只追加新帧；死数据过半时在快照前压缩为新一代文件。
+/// 日志只追加，冻结副本共享文件句柄与索引即可读取冻结时刻的内容。
+#[derive(Clone)]
+pub struct LogStore {
+    dir: PathBuf,
+    generation: u64,
+    writer: Arc<File>,
+    reader: Arc<Mutex<File>>,
+    index: LogIndex,
+    len: u64,
+    live_bytes: u64,
+    /// 已被新一代取代、待下一次快照落盘后删除的文件
+    stale: Vec<PathBuf>,
+    /// 低于该大小不压缩
+    pub compaction_min_bytes: u64,
+}
+
+impl LogStore {
+    pub fn log_path(dir: &Path, generation: u64) -> PathBuf {
+        dir.join(format!("cells-{:06}.log", generation))
+    }
+
+    /// 在空目录中创建新的单元格日志；目录中已有日志时拒绝覆盖
+    pub fn create(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create cell store {}: {}", dir.display(), e))?;
+        if !list_generations(dir)?.is_empty() {
+            return Err(format!("Cell store {} already contains data; refusing to overwrite.", dir.display()));
+        }
+        File::create(Self::log_path(dir, 0)).map_err(|e| e.to_string())?;
+        Self::open_at(dir, 0, 0)
+    }
+
+    /// 打开快照引用的日志代：截掉 len 之后的写入 (它们在 WAL 中，恢复时重放)，
+    /// 扫描重建索引，并删除未被引用的其他代文件
+    pub fn open_at(dir: &Path, generation: u64, len: u64) -> Result<Self, String> {
+        let path = Self::log_path(dir, generation);
+        let on_disk = std::fs::metadata(&path)
+            .map_err(|e| format!("Cell store {} unavailable: {}", path.display(), e))?
+            .len();
+        if on_disk < len {
+            return Err(format!("Cell store {} truncated: {} of {} bytes.", path.display(), on_disk, len));
+        }
+        if on_disk > len {
+            warn!("⚠️  Cell store has {} bytes past the snapshot. Truncating (WAL replay restores them).", on_disk - len);
+            let file = OpenOptions::new().write(true).open(&path).map_err(|e| e.to_string())?;
+            file.set_len(len).map_err(|e| e.to_string())?;
+            file.sync_all().map_err(|e| e.to_string())?;
+        }
+
+        let (index, live_bytes) = scan_log(&path, len)?;
+        for (other, stale) in list_generations(dir)? {
+            if other != generation {
+                let _ = std::fs::remove_file(stale);
+            }
+        }
+
+        Ok(LogStore {
+            dir: dir.to_path_buf(),
+            generation,
+            writer: Arc::new(OpenOptions::new().append(true).open(&path).map_err(|e| e.to_string())?),
+            reader: Arc::new(Mutex::new(File::open(&path).map_err(|e| e.to_string())?)),
+            index,
+            len,
+            live_bytes,
+            stale: Vec::new(),
+            compaction_min_bytes: COMPACTION_MIN_BYTES,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// 日志总字节数与其中仍被引用的字节数
+    pub fn usage(&self) -> (u64, u64) {
+        (self.len, self.live_bytes)
+    }
+
+    fn append(&mut self, record: &LogRecord) -> Result<(u64, u32), String> {
+        let payload = bincode::serialize(record).map_err(|e| e.to_string())?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER + payload.len());
+        encode_frame(&payload, &mut frame);
+        self.writer.as_ref().write_all(&frame).map_err(|e| e.to_string())?;
+        let offset = self.len;
+        self.len += frame.len() as u64;
+        Ok((offset, payload.len() as u32))
+    }
+
+    fn read_cell(&self, offset: u64, payload_len: u32) -> Result<CellRecord, String> {
+        let mut frame = vec![0u8; FRAME_HEADER + payload_len as usize];
+        {
+            let mut file = self.reader.lock().map_err(|_| "Cell store lock poisoned.".to_string())?;
+            file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+            file.read_exact(&mut frame).map_err(|e| e.to_string())?;
+        }
+        let payload = &frame[FRAME_HEADER..];
+        if checksum(payload) != frame[4..FRAME_HEADER] {
+            return Err(format!("Cell store checksum mismatch at offset {}.", offset));
+        }
+        let record: LogRecord = bincode::deserialize(payload).map_err(|e| e.to_string())?;
+        record.cell.ok_or_else(|| format!("Cell store index points at a tombstone (offset {}).", offset))
+    }
+
+    fn retire(&mut self, key: PackedCoord) {
+        if let Some((_, old)) = self.index.remove(&key) {
+            self.live_bytes -= (FRAME_HEADER + old as usize) as u64;
+        }
+    }
+}
+
+impl TensorStore for LogStore {
+    fn backend(&self) -> StoreBackend {
+        StoreBackend::Log
+    }
+
+    fn get(&self, key: PackedCoord) -> Result<Option<Cow<'_, CellRecord>>, String> {
+        match self.index.get(&key) {
+            Some(&(offset, len)) => Ok(Some(Cow::Owned(self.read_cell(offset, len)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn put(&mut self, key: PackedCoord, cell: CellRecord) -> Result<(), String> {
+        let (offset, len) = self.append(&LogRecord { key, cell: Some(cell) })?;
+        self.retire(key);
+        self.index.insert(key, (offset, len));
+        self.live_bytes += (FRAME_HEADER + len as usize) as u64;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: PackedCoord) -> Result<(), String> {
+        if !self.index.contains_key(&key) {
+            return Ok(());
+        }
+        self.append(&LogRecord { key, cell: None })?;
+        self.retire(key);
+        Ok(())
+    }
+
+    fn contains(&self, key: PackedCoord) -> bool {
+        self.index.contains_key(&key)
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn range(&self, range: Range<PackedCoord>) -> CellIter<'_> {
+        Box::new(self.index.range(range).map(move |(&k, &(offset, len))| {
+            self.read_cell(offset, len).map(|c| (k, Cow::Owned(c)))
+        }))
+    }
+
+    fn keys(&self, range: Range<PackedCoord>) -> Box<dyn Iterator<Item = PackedCoord> + '_> {
+        Box::new(self.index.range(range).map(|(k, _)| *k))
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    fn snapshot(&self) -> Result<StoreSnapshot<'_>, String> {
+        self.writer.sync_data().map_err(|e| e.to_string())?;
+        Ok(StoreSnapshot::Log { dir: self.dir.clone(), generation: self.generation, len: self.len })
+    }
+
+    /// 将活记录按坐标顺序写入新一代文件；旧文件在下一次快照落盘后删除
+    fn compact(&mut self) -> Result<(), String> {
+        if self.len < self.compaction_min_bytes || self.len <= 2 * self.live_bytes {
+            return Ok(());
+        }
+        let next = self.generation + 1;
+        let path = Self::log_path(&self.dir, next);
+        let mut out = BufWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+        let mut index = LogIndex::new();
+        let mut offset = 0u64;
+        for (&key, &(at, len)) in &self.index {
+            let record = LogRecord { key, cell: Some(self.read_cell(at, len)?) };
+            let payload = bincode::serialize(&record).map_err(|e| e.to_string())?;
+            let mut frame = Vec::with_capacity(FRAME_HEADER + payload.len());
+            encode_frame(&payload, &mut frame);
+            out.write_all(&frame).map_err(|e| e.to_string())?;
+            index.insert(key, (offset, payload.len() as u32));
+            offset += frame.len() as u64;
+        }
+        out.into_inner().map_err(|e| e.to_string())?.sync_all().map_err(|e| e.to_string())?;
+
+        info!("🧹 Compacted cell store: {} → {} bytes (generation {}).", self.len, offset, next);
+        self.stale.push(Self::log_path(&self.dir, self.generation));
+        self.writer = Arc::new(OpenOptions::new().append(true).open(&path).map_err(|e| e.to_string())?);
+        self.reader = Arc::new(Mutex::new(File::open(&path).map_err(|e| e.to_string())?));
+        self.generation = next;
+        self.index = index;
+        self.len = offset;
+        self.live_bytes = offset;
+        Ok(())
+    }
+
+    fn release_stale(&mut self) -> Result<(), String> {
+        for path in self.stale.drain(..) {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// 已打开的句柄在文件被压缩删除后仍然有效 (Unix 语义)
+    fn freeze(&self) -> CellStore {
+        CellStore::new(LogStore { stale: Vec::new(), ..self.clone() })
+    }
+
+    /// 与当前目录同级的新目录：htp_cells → htp_cells.{tag}
+    fn empty_sibling(&self, tag: &str) -> Result<CellStore, String> {
+        let name = self.dir.file_name().and_then(|n| n.to_str()).unwrap_or("cells");
+        let base = name.split('.').next().unwrap_or(name);
+        let dir = self.dir.with_file_name(format!("{}.{}", base, tag));
+        // 同一 epoch 上中止的重建留下的目录不被任何快照引用，可直接丢弃
+        if dir != self.dir && dir.exists() {
+            std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to clear stale cell store {}: {}", dir.display(), e))?;
+        }
+        LogStore::create(&dir).map(CellStore::new)
+    }
+}
+
+/// 目录中所有 cells-*.log 文件及其代号
+fn list_generations(dir: &Path) -> Result<Vec<(u64, PathBuf)>, String> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let generation = path.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("cells-"))
+            .and_then(|n| n.strip_suffix(".log"))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(g) = generation {
+            found.push((g, path));
+        }
+    }
+    Ok(found)
+}
+
+/// 扫描日志前 len 字节重建索引；快照范围内的任何损坏帧都是致命错误
+fn scan_log(path: &Path, len: u64) -> Result<(LogIndex, u64), String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut index = LogIndex::new();
+    let mut live_bytes = 0u64;
+    let mut offset = 0u64;
+
+    while offset < len {
+        let corrupt = || format!("Cell store {} corrupted at offset {}.", path.display(), offset);
+        let mut header = [0u8; FRAME_HEADER];
+        reader.read_exact(&mut header).map_err(|_| corrupt())?;
+        let payload_len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        if payload_len > MAX_FRAME_LEN {
+            return Err(corrupt());
+        }
+        let mut payload = vec![0u8; payload_len];
+        reader.read_exact(&mut payload).map_err(|_| corrupt())?;
+        if checksum(&payload) != header[4..] {
+            return Err(corrupt());
+        }
+        let head: LogRecordHead = bincode::deserialize(&payload).map_err(|_| corrupt())?;
+
+        let frame_len = (FRAME_HEADER + payload_len) as u64;
+        if let Some((_, old)) = index.remove(&head.key) {
+            live_bytes -= (FRAME_HEADER + old as usize) as u64;
+        }
+        if head.present != 0 {
+            index.insert(head.key, (offset, payload_len as u32));
+            live_bytes += frame_len;
+        }
+        offset += frame_len;
+    }
+    if offset != len {
+        return Err(format!("Cell store {} has a frame crossing the snapshot boundary.", path.display()));
+    }
+    Ok((index, live_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::testing::Scratch;
+    use rug::Integer;
+
+    fn cell(n: u64) -> CellRecord {
+        let d = Integer::from(-23);
+        CellRecord { value: AffineTuple { p_factor: Integer::from(n), ..AffineTuple::identity(&d) }, members: Vec::new() }
+    }
+
+    fn contents(store: &LogStore) -> Vec<(PackedCoord, Integer)> {
+        store.range(PackedCoord(0)..PackedCoord(u128::MAX))
+            .map(|entry| entry.map(|(k, c)| (k, c.value.p_factor.clone())))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn snapshot_point(store: &LogStore) -> (u64, u64) {
+        match store.snapshot().unwrap() {
+            StoreSnapshot::Log { generation, len, .. } => (generation, len),
+            StoreSnapshot::Memory(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn reopen_restores_the_snapshot_and_drops_later_writes() {
+        let scratch = Scratch::new("logstore-reopen");
+        let mut store = LogStore::create(&scratch.0).unwrap();
+        for k in 0..10u128 {
+            store.put(PackedCoord(k), cell(k as u64)).unwrap();
+        }
+        store.put(PackedCoord(3), cell(33)).unwrap();
+        store.remove(PackedCoord(4)).unwrap();
+        let expected = contents(&store);
+        let (generation, len) = snapshot_point(&store);
+
+        // 快照之后的写入只在 WAL 中，重新打开时截掉
+        store.put(PackedCoord(100), cell(100)).unwrap();
+        drop(store);
+
+        let reopened = LogStore::open_at(&scratch.0, generation, len).unwrap();
+        assert_eq!(contents(&reopened), expected);
+        assert_eq!(reopened.len(), 9);
+        assert!(!reopened.contains(PackedCoord(4)));
+        assert_eq!(std::fs::metadata(LogStore::log_path(&scratch.0, generation)).unwrap().len(), len);
+        assert!(LogStore::create(&scratch.0).is_err());
+    }
+
+    #[test]
+    fn compaction_keeps_live_cells_and_retires_the_old_generation() {
+        let scratch = Scratch::new("logstore-compact");
+        let mut store = LogStore::create(&scratch.0).unwrap();
+        store.compaction_min_bytes = 0;
+        for round in 0..5u64 {
+            for k in 0..8u128 {
+                store.put(PackedCoord(k), cell(round * 100 + k as u64)).unwrap();
+            }
+        }
+        store.remove(PackedCoord(7)).unwrap();
+        let expected = contents(&store);
+        let (total, live) = store.usage();
+        assert!(total > 2 * live);
+
+        let frozen = store.freeze();
+        store.compact().unwrap();
+        assert_eq!(store.generation(), 1);
+        assert_eq!(store.usage(), (live, live));
+        assert_eq!(contents(&store), expected);
+
+        // 旧代文件在快照落盘后删除；冻结副本仍可读取
+        let old = LogStore::log_path(&scratch.0, 0);
+        assert!(old.exists());
+        let (generation, len) = snapshot_point(&store);
+        store.release_stale().unwrap();
+        assert!(!old.exists());
+        assert_eq!(frozen.len(), expected.len());
+        assert_eq!(frozen.get(PackedCoord(2)).unwrap().unwrap().value.p_factor, 402);
+
+        // 未达到死数据阈值时不压缩
+        store.compact().unwrap();
+        assert_eq!(store.generation(), 1);
+
+        drop(store);
+        let reopened = LogStore::open_at(&scratch.0, generation, len).unwrap();
+        assert_eq!(contents(&reopened), expected);
+    }
+
+    #[test]
+    fn corruption_inside_the_snapshot_is_fatal() {
+        let scratch = Scratch::new("logstore-corrupt");
+        let mut store = LogStore::create(&scratch.0).unwrap();
+        for k in 0..4u128 {
+            store.put(PackedCoord(k), cell(k as u64)).unwrap();
+        }
+        let (generation, len) = snapshot_point(&store);
+        drop(store);
+
+        let path = LogStore::log_path(&scratch.0, generation);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[FRAME_HEADER + 2] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(LogStore::open_at(&scratch.0, generation, len).is_err());
+        assert!(LogStore::open_at(&scratch.0, generation, len + 1).is_err());
+    }
+}
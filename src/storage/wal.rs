@@ -0,0 +1,142 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::topology::membership::CellMember;
+use crate::topology::tensor::PackedCoord;
+use serde::{Serialize, Deserialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 帧头：4 字节长度 + 8 字节校验和 (blake3 截断)
+pub const FRAME_HEADER: usize = 12;
+/// 单帧上限，防止损坏的长度字段导致巨量分配
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// 张量状态变更。记录派生后的成员数据而非明文 ID，重放无需重新 Hash-to-Prime。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum WalOp {
+    Insert {
+        key: PackedCoord,
+        member: CellMember,
+        replace: bool,
+    },
+    Revoke {
+        key: PackedCoord,
+        id_digest: [u8; 32],
+    },
+    /// epoch 边界；之前尚未提交的操作在恢复时丢弃
+    Commit {
+        epoch: u64,
+        timestamp: u64,
+    },
+    /// [NEW]: 成员的 128-bit 坐标地址 (不影响根，重分片时据此重新取模)
+    Address {
+        id_digest: [u8; 32],
+        address: u128,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalEntry {
+    pub seq: u64,
+    pub op: WalOp,
+}
+
+pub fn checksum(payload: &[u8]) -> [u8; 8] {
+    let hash = blake3::hash(payload);
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&hash.as_bytes()[..8]);
+    out
+}
+
+/// 追加一帧：长度 | 校验和 | 载荷 (WAL 与单元格日志共用)
+pub fn encode_frame(payload: &[u8], buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&checksum(payload));
+    buf.extend_from_slice(payload);
+}
+
+/// 只追加的预写日志
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl WriteAheadLog {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open WAL {}: {}", path.display(), e))?;
+        Ok(WriteAheadLog { path: path.to_path_buf(), file })
+    }
+
+    /// 追加一组记录并 fsync；返回后记录即已持久化
+    pub fn append(&mut self, entries: &[WalEntry]) -> Result<(), String> {
+        let mut buf = Vec::new();
+        for entry in entries {
+            let payload = bincode::serialize(entry).map_err(|e| e.to_string())?;
+            encode_frame(&payload, &mut buf);
+        }
+        self.file.write_all(&buf).map_err(|e| e.to_string())?;
+        self.file.sync_data().map_err(|e| e.to_string())
+    }
+
+    /// 快照落盘后清空日志
+    pub fn reset(&mut self) -> Result<(), String> {
+        self.file.set_len(0).map_err(|e| e.to_string())?;
+        self.file.sync_all().map_err(|e| e.to_string())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// 读取日志中所有完整且校验通过的记录，附带每条记录结束处的字节偏移。
+/// 遇到截断或校验失败的尾部 (崩溃时写了一半的帧) 即停止。
+/// [FIX]: 坏帧之后若仍能找到完整记录，则是日志中段损坏而非撕裂的尾帧，截断会丢弃已确认的提交，直接报错
+pub fn read_wal(path: &Path) -> Result<Vec<(WalEntry, u64)>, String> {
+    let bytes = match std::fs::read(path) {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut entries = Vec::new();
+    let mut offset = 0usize;
+
+    while offset < bytes.len() {
+        match decode_frame(&bytes[offset..]) {
+            Some((entry, len)) => {
+                offset += len;
+                entries.push((entry, offset as u64));
+            },
+            None => {
+                if let Some(next) = (offset + 1..bytes.len()).find(|&at| decode_frame(&bytes[at..]).is_some()) {
+                    return Err(format!(
+                        "WAL {} is corrupt at byte {} but holds valid records from byte {}; refusing to truncate it. Restore the log or move it aside.",
+                        path.display(), offset, next
+                    ));
+                }
+                break;
+            },
+        }
+    }
+    Ok(entries)
+}
+
+/// 解码缓冲区开头的一帧；不完整、超长、校验失败或无法反序列化时返回 None
+fn decode_frame(buf: &[u8]) -> Option<(WalEntry, usize)> {
+    let header = buf.get(..FRAME_HEADER)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    if len > MAX_FRAME_LEN {
+        return None;
+    }
+    let payload = buf.get(FRAME_HEADER..FRAME_HEADER + len)?;
+    if checksum(payload) != header[4..] {
+        return None;
+    }
+    let entry = bincode::deserialize(payload).ok()?;
+    Some((entry, FRAME_HEADER + len))
+}
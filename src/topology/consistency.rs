@@ -0,0 +1,199 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use super::segment_tree::combine;
+use super::tensor::Coordinate;
+use super::witness::{root_from_paths, NodeId};
+use rug::Integer;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+/// 单个单元格在一个 epoch 内的变化：提交前与提交后的有序成员链
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CellTransition {
+    pub coord: Coordinate,
+    pub before: Vec<AffineTuple>,
+    pub after: Vec<AffineTuple>,
+}
+
+impl CellTransition {
+    /// 旧成员链是新成员链的前缀，即本单元格只发生了追加
+    pub fn is_append_only(&self) -> bool {
+        self.before.len() <= self.after.len()
+            && self.before.iter().zip(&self.after).all(|(x, y)| x.p_factor == y.p_factor && x.q_shift == y.q_shift)
+    }
+}
+
+/// 一个 epoch 的变更摘要 (类似 Certificate Transparency 的一致性证明单元)
+/// 变更单元格在提交前后的成员链，以及这些单元格路径之外的兄弟节点 (本 epoch 内未变化，两侧共用)。
+/// 以提交前的成员链重建根应得到上一 epoch 的根，以提交后的成员链重建应得到本 epoch 的根。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EpochDelta {
+    pub epoch: u64,
+    pub side_length: usize,
+    /// 按坐标字典序排列
+    pub cells: Vec<CellTransition>,
+    pub siblings: BTreeMap<NodeId, Option<AffineTuple>>,
+}
+
+/// epoch from → to 的一致性证明：依次包含 from+1 ..= to 的变更摘要
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub from: u64,
+    pub to: u64,
+    pub deltas: Vec<EpochDelta>,
+}
+
+/// 校验通过后的统计：removed > 0 表示期间存在显式撤销 (非静默)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    pub epochs: u64,
+    pub appended: u64,
+    pub rewritten_cells: u64,
+}
+
+impl ConsistencyReport {
+    pub fn is_append_only(&self) -> bool {
+        self.rewritten_cells == 0
+    }
+}
+
+fn fold_chain(chain: &[AffineTuple], discriminant: &Integer) -> Result<Option<AffineTuple>, String> {
+    let mut acc: Option<AffineTuple> = None;
+    for t in chain {
+        acc = combine(acc.as_ref(), Some(t), discriminant)?;
+    }
+    Ok(acc)
+}
+
+/// 以每个变更单元格的 before 或 after 成员链重建根
+fn rebuild_root<'a, F>(delta: &'a EpochDelta, pick: F, discriminant: &Integer) -> Result<AffineTuple, String>
+where
+    F: Fn(&'a CellTransition) -> &'a [AffineTuple],
+{
+    let cells = delta.cells.iter()
+        .map(|cell| Ok((cell.coord.clone(), fold_chain(pick(cell), discriminant)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    root_from_paths(&cells, &delta.siblings, delta.side_length, discriminant)
+}
+
+fn same_root(x: &AffineTuple, y: &AffineTuple) -> bool {
+    x.p_factor == y.p_factor && x.q_shift == y.q_shift
+}
+
+/// 客户端验证器：确认 to_root 是 from_root 经过证明中列出的变更得到的，
+/// 未列出的单元格保持不变 (无静默删除或重排)
+pub fn verify_consistency(
+    from_root: &AffineTuple,
+    to_root: &AffineTuple,
+    proof: &ConsistencyProof,
+    discriminant: &Integer,
+) -> Result<ConsistencyReport, String> {
+    if proof.to < proof.from || proof.deltas.len() as u64 != proof.to - proof.from {
+        return Err(format!("Malformed consistency proof: {} deltas for epochs {} -> {}.", proof.deltas.len(), proof.from, proof.to));
+    }
+
+    let mut report = ConsistencyReport::default();
+    let mut current = from_root.clone();
+    for (i, delta) in proof.deltas.iter().enumerate() {
+        let expected_epoch = proof.from + 1 + i as u64;
+        if delta.epoch != expected_epoch {
+            return Err(format!("Consistency proof out of order: expected epoch {}, found {}.", expected_epoch, delta.epoch));
+        }
+        let before = rebuild_root(delta, |c| &c.before, discriminant)
+            .map_err(|e| format!("Malformed delta for epoch {}: {}", delta.epoch, e))?;
+        if !same_root(&before, &current) {
+            return Err(format!("Consistency check failed at epoch {}: delta does not start from the previous root.", delta.epoch));
+        }
+        current = rebuild_root(delta, |c| &c.after, discriminant)?;
+
+        for cell in &delta.cells {
+            if cell.is_append_only() {
+                report.appended += (cell.after.len() - cell.before.len()) as u64;
+            } else {
+                report.rewritten_cells += 1;
+            }
+        }
+        report.epochs += 1;
+    }
+
+    if !same_root(&current, to_root) {
+        return Err("Consistency check failed: replayed deltas do not reach the target root.".to_string());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::tensor::HyperTensor;
+    use crate::topology::testing::{discriminant, register};
+
+    /// epoch 1、2 各注册 8 人，epoch 3 撤销 user-3
+    fn history() -> (HyperTensor, Vec<AffineTuple>) {
+        let mut tensor = HyperTensor::new(3, 4, discriminant());
+        let mut roots = vec![AffineTuple::identity(&tensor.discriminant)];
+        for batch in 0..2 {
+            for i in 0..8 {
+                register(&mut tensor, &format!("user-{}", batch * 8 + i)).unwrap();
+            }
+            roots.push(tensor.commit_epoch().unwrap().unwrap().root);
+        }
+        assert!(tensor.revoke("user-3").unwrap());
+        roots.push(tensor.commit_epoch().unwrap().unwrap().root);
+        (tensor, roots)
+    }
+
+    #[test]
+    fn honest_deltas_verify() {
+        let (tensor, roots) = history();
+        let d = &tensor.discriminant;
+
+        let proof = tensor.consistency_proof(0, 2).unwrap();
+        let report = verify_consistency(&roots[0], &roots[2], &proof, d).unwrap();
+        assert_eq!(report, ConsistencyReport { epochs: 2, appended: 16, rewritten_cells: 0 });
+
+        let proof = tensor.consistency_proof(1, 3).unwrap();
+        let report = verify_consistency(&roots[1], &roots[3], &proof, d).unwrap();
+        assert_eq!(report.epochs, 2);
+        assert_eq!(report.rewritten_cells, 1);
+        assert!(!report.is_append_only());
+
+        let empty = tensor.consistency_proof(3, 3).unwrap();
+        assert!(verify_consistency(&roots[3], &roots[3], &empty, d).is_ok());
+    }
+
+    #[test]
+    fn forged_deltas_are_rejected() {
+        let (tensor, roots) = history();
+        let d = &tensor.discriminant;
+        let honest = tensor.consistency_proof(2, 3).unwrap();
+
+        // 隐藏撤销：把被改写单元格的旧成员链换成新成员链
+        let mut hidden = honest.clone();
+        let cell = hidden.deltas[0].cells.iter_mut().find(|c| !c.is_append_only()).unwrap();
+        cell.before = cell.after.clone();
+        assert!(verify_consistency(&roots[2], &roots[3], &hidden, d).is_err());
+
+        // 篡改兄弟节点
+        let mut tampered = honest.clone();
+        let sibling = tampered.deltas[0].siblings.values_mut().find(|v| v.is_some()).unwrap();
+        *sibling = None;
+        assert!(verify_consistency(&roots[2], &roots[3], &tampered, d).is_err());
+
+        // 缺少兄弟节点
+        let mut incomplete = honest.clone();
+        let id = *incomplete.deltas[0].siblings.keys().next().unwrap();
+        incomplete.deltas[0].siblings.remove(&id);
+        assert!(verify_consistency(&roots[2], &roots[3], &incomplete, d).is_err());
+
+        // 错误的起点或终点
+        assert!(verify_consistency(&roots[1], &roots[3], &honest, d).is_err());
+        assert!(verify_consistency(&roots[2], &roots[2], &honest, d).is_err());
+
+        // 跳过中间 epoch
+        let mut skipped = tensor.consistency_proof(1, 3).unwrap();
+        skipped.deltas.remove(0);
+        assert!(verify_consistency(&roots[1], &roots[3], &skipped, d).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use serde::{Serialize, Deserialize};
+
+/// 默认保留的历史根数量 (环形淘汰最旧记录)
+pub const DEFAULT_ROOT_HISTORY: usize = 4096;
+
+/// 已提交 epoch 的不可变记录
+/// epoch 0 为创世状态 (空张量，根为单位元)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EpochRecord {
+    pub epoch: u64,
+    pub root: AffineTuple,
+    /// 提交时刻 (Unix 秒)
+    pub timestamp: u64,
+    /// 本 epoch 相对上一 epoch 的变更数 (注册 + 撤销)
+    pub changes: u64,
+}
+
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
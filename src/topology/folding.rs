@@ -1,8 +1,44 @@
 // COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
 
-use super::tensor::HyperTensor;
+use super::tensor::{Coordinate, HyperTensor};
+use super::segment_tree::SparseSegmentTree;
+use super::witness::{pack_prefix, MembershipWitness, NodeId};
+use super::membership::member_digest;
 use crate::core::affine::AffineTuple;
-use std::collections::HashMap;
+use rug::Integer;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// 分维度的增量聚合索引
+/// levels[k] 将前缀 coord[0..k] 映射到一棵沿第 k 维的稀疏线段树，
+/// 其叶子 i 为前缀 coord[0..k] ++ [i] 的子张量聚合值。
+/// 单个单元格变化只需沿路径重算 O(d · log L) 个节点。
+/// 持久化映射 + 共享树：克隆为 O(d)，之后的更新只复制被修改的树 (冻结快照与活动张量共享其余部分)。
+#[derive(Clone, Debug, Default)]
+pub struct AggregateIndex {
+    levels: Vec<im::HashMap<Coordinate, Arc<SparseSegmentTree>>>,
+}
+
+impl AggregateIndex {
+    pub fn clear(&mut self) {
+        self.levels.clear();
+    }
+
+    /// 全局聚合；None 表示单位元
+    pub fn root(&self) -> Option<&AffineTuple> {
+        self.levels.first()?.get(&Vec::new())?.root()
+    }
+
+    /// 前缀 coord[0..k] 对应的子张量聚合值
+    pub fn subtree(&self, prefix: &[usize]) -> Option<&AffineTuple> {
+        let k = prefix.len();
+        if k == 0 {
+            return self.root();
+        }
+        self.levels.get(k - 1)?.get(&prefix[..k - 1])?.leaf(prefix[k - 1])
+    }
+}
 
 impl HyperTensor {
     pub fn calculate_global_root(&mut self) -> Result<AffineTuple, String> {
@@ -10,51 +46,304 @@ impl HyperTensor {
             return Ok(root.clone());
         }
 
-        let root = self.compute_root_internal()?;
+        // [PERF FIX]: 只沿脏单元格的路径增量重算，而非全量折叠
+        self.flush_aggregates()?;
+        let root = match self.aggregates.root() {
+            Some(r) => r.clone(),
+            None => AffineTuple::identity(&self.discriminant),
+        };
         self.cached_root = Some(root.clone());
         Ok(root)
     }
 
-    pub fn compute_root_internal(&self) -> Result<AffineTuple, String> {
-        // [PERF FIX]: 移除旧的 build_active_prefixes (内存炸弹)，改为稀疏递归
-        let root = self.fold_sparse(0, &self.data)?;
-        Ok(root)
+    /// 将脏单元格的变化传播到各层聚合；失败时保留脏集合以便重试
+    pub fn flush_aggregates(&mut self) -> Result<(), String> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+        let dims = self.dimensions;
+        if self.aggregates.levels.len() != dims {
+            self.aggregates.levels = vec![im::HashMap::new(); dims];
+        }
+
+        let mut changed: BTreeSet<Coordinate> = self.dirty.iter().map(|&key| self.unpack(key)).collect();
+        for k in (0..dims).rev() {
+            // 按前缀 coord[0..k] 分组，一棵树内的多个叶子一次性更新
+            let mut updates: BTreeMap<Coordinate, Vec<(usize, Option<AffineTuple>)>> = BTreeMap::new();
+            for c in &changed {
+                let value = if k + 1 == dims {
+                    self.data.get(self.pack(c))?.map(|cell| cell.into_owned().value)
+                } else {
+                    self.aggregates.levels[k + 1].get(c).and_then(|t| t.root()).cloned()
+                };
+                updates.entry(c[..k].to_vec()).or_default().push((c[k], value));
+            }
+
+            let level = &mut self.aggregates.levels[k];
+            for (prefix, leaves) in updates.iter_mut() {
+                let tree = level.entry(prefix.clone()).or_insert_with(|| Arc::new(SparseSegmentTree::new(k, self.side_length)));
+                let tree = Arc::make_mut(tree);
+                tree.update(std::mem::take(leaves), &self.discriminant)?;
+                if tree.is_empty() {
+                    level.remove(prefix);
+                }
+            }
+            changed = updates.into_keys().collect();
+        }
+
+        self.dirty.clear();
+        Ok(())
+    }
+
+    /// 构造成员见证 (要求聚合索引已刷新，即 dirty 为空)；非成员返回 None
+    pub fn membership_witness(&self, user_id: &str) -> Result<Option<MembershipWitness>, String> {
+        if !self.dirty.is_empty() {
+            return Err("Aggregates not flushed; compute the global root first.".to_string());
+        }
+        let coord = self.map_id_to_coord_hash(user_id);
+        let digest = member_digest(user_id);
+        let cell = match self.data.get(self.pack(&coord))? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let depth = match cell.members.iter().find(|m| m.id_digest == digest) {
+            Some(m) => m.depth,
+            None => return Ok(None),
+        };
+
+        let chain = cell.members.iter().map(|m| m.tuple()).collect();
+        Ok(Some(self.path_witness(&coord, depth, chain)))
+    }
+
+    /// 沿 coord 的各维线段树路径收集兄弟节点，组装见证
+    pub fn path_witness(&self, coord: &Coordinate, depth: u32, bucket_chain: Vec<AffineTuple>) -> MembershipWitness {
+        MembershipWitness {
+            epoch: self.epoch,
+            coord: coord.clone(),
+            side_length: self.side_length,
+            width: self.side_length.max(1).next_power_of_two(),
+            depth,
+            bucket_chain,
+            siblings: self.path_siblings(std::slice::from_ref(coord)),
+        }
+    }
+
+    /// 自单元格起逐层向上的子张量聚合值：coord[0..d], coord[0..d-1], ..., 全局根 (要求聚合索引已刷新)
+    pub fn path_aggregates(&self, coord: &Coordinate) -> Vec<AffineTuple> {
+        (0..=self.dimensions).rev()
+            .map(|k| self.aggregates.subtree(&coord[..k]).cloned().unwrap_or_else(|| AffineTuple::identity(&self.discriminant)))
+            .collect()
+    }
+
+    /// 给定单元格路径之外、与路径相邻的兄弟节点的当前值 (见证与一致性证明共用)，要求聚合索引已刷新
+    pub fn path_siblings(&self, coords: &[Coordinate]) -> BTreeMap<NodeId, Option<AffineTuple>> {
+        let width = self.side_length.max(1).next_power_of_two();
+        let walk = |coord: &Coordinate, level: usize| {
+            let packed = pack_prefix(&coord[..level], self.side_length);
+            let mut pos = width + coord[level];
+            std::iter::from_fn(move || {
+                let id = (pos > 1).then_some(NodeId { level: level as u32, prefix: packed, pos: pos as u64 });
+                pos >>= 1;
+                id
+            })
+        };
+
+        let mut on_path = BTreeSet::new();
+        for coord in coords {
+            for level in 0..self.dimensions {
+                on_path.extend(walk(coord, level));
+            }
+        }
+        let mut siblings = BTreeMap::new();
+        for coord in coords {
+            for level in 0..self.dimensions {
+                let tree = self.aggregates.levels.get(level).and_then(|l| l.get(&coord[..level]));
+                for id in walk(coord, level) {
+                    let sibling = NodeId { pos: id.pos ^ 1, ..id };
+                    if !on_path.contains(&sibling) {
+                        siblings.entry(sibling).or_insert_with(|| tree.and_then(|t| t.node(sibling.pos as usize)).cloned());
+                    }
+                }
+            }
+        }
+        siblings
     }
 
-    // 内存友好的稀疏折叠算法 (O(N) 内存占用)
-    fn fold_sparse(
-        &self,
-        current_dim: usize,
-        relevant_data: &HashMap<Vec<usize>, AffineTuple>
-    ) -> Result<AffineTuple, String> {
-        if relevant_data.is_empty() {
-             return Ok(AffineTuple::identity(&self.discriminant));
+    /// 给定单元格路径上所有节点的当前值 (见证更新数据)，要求聚合索引已刷新
+    pub fn path_nodes(&self, coords: &[Coordinate]) -> Vec<(NodeId, Option<AffineTuple>)> {
+        let width = self.side_length.max(1).next_power_of_two();
+        let mut nodes = BTreeMap::new();
+        for coord in coords {
+            for level in 0..self.dimensions {
+                let prefix = &coord[..level];
+                let tree = self.aggregates.levels.get(level).and_then(|l| l.get(prefix));
+                let packed = pack_prefix(prefix, self.side_length);
+                let mut pos = width + coord[level];
+                while pos >= 1 {
+                    let id = NodeId { level: level as u32, prefix: packed, pos: pos as u64 };
+                    nodes.entry(id).or_insert_with(|| tree.and_then(|t| t.node(pos)).cloned());
+                    pos >>= 1;
+                }
+            }
         }
+        nodes.into_iter().collect()
+    }
+
+    /// 丢弃聚合索引并将所有单元格标记为脏 (如从磁盘加载后)
+    pub fn rebuild_aggregates(&mut self) {
+        self.aggregates.clear();
+        self.dirty = self.data.keys(self.full_range()).collect();
+        self.cached_root = None;
+    }
+
+    pub fn compute_root_internal(&self) -> Result<AffineTuple, String> {
+        self.fold_prefix(&[])
+    }
+
+    /// 前缀 coord[0..k] 的子张量聚合值，不经聚合索引直接由单元格重建 (用于校验增量索引)
+    /// 打包坐标按字典序存储，子张量即一段连续区间，逐维分组建树即可。
+    pub fn fold_prefix(&self, prefix: &[usize]) -> Result<AffineTuple, String> {
+        let cells = self.data.range(self.prefix_range(prefix))
+            .map(|entry| entry.map(|(key, cell)| (self.unpack(key), cell.into_owned().value)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let root = fold_level(prefix.len(), &cells, self.side_length, &self.discriminant)?;
+        Ok(root.unwrap_or_else(|| AffineTuple::identity(&self.discriminant)))
+    }
+}
+
+/// 有序单元格在第 level 维上按坐标分组，逐组递归得到子树根，再建该维的线段树
+fn fold_level(level: usize, cells: &[(Coordinate, AffineTuple)], side_length: usize, discriminant: &Integer) -> Result<Option<AffineTuple>, String> {
+    match cells {
+        [] => return Ok(None),
+        [(coord, value)] if coord.len() == level => return Ok(Some(value.clone())),
+        _ => {},
+    }
+    let mut leaves = Vec::new();
+    for group in cells.chunk_by(|x, y| x.0[level] == y.0[level]) {
+        leaves.push((group[0].0[level], fold_level(level + 1, group, side_length, discriminant)?));
+    }
+    build_tree(level, leaves, side_length, discriminant)
+}
+
+fn build_tree(level: usize, leaves: Vec<(usize, Option<AffineTuple>)>, side_length: usize, discriminant: &Integer) -> Result<Option<AffineTuple>, String> {
+    let mut tree = SparseSegmentTree::new(level, side_length);
+    tree.update(leaves, discriminant)?;
+    Ok(tree.root().cloned())
+}
+
+/// 并行维度折叠器
+/// 第 0 维上的各子张量相互独立，可由 rayon 工作窃取并行建树，再合并为第 0 维的线段树。
+pub struct ParallelFolder {
+    pool: rayon::ThreadPool,
+}
+
+impl ParallelFolder {
+    /// workers = 0 时使用 rayon 默认值 (逻辑 CPU 数)
+    pub fn new(workers: usize) -> Result<Self, String> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(workers)
+            .thread_name(|i| format!("htp-fold-{}", i))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(ParallelFolder { pool })
+    }
+
+    pub fn workers(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// 与 compute_root_internal 结果一致
+    pub fn fold(&self, tensor: &HyperTensor) -> Result<AffineTuple, String> {
+        let discriminant = &tensor.discriminant;
+        let cells = tensor.data.range(tensor.full_range())
+            .map(|entry| entry.map(|(key, cell)| (tensor.unpack(key), cell.into_owned().value)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let groups: Vec<&[(Coordinate, AffineTuple)]> = cells.chunk_by(|x, y| x.0[0] == y.0[0]).collect();
+
+        self.pool.install(|| {
+            let leaves = groups
+                .par_iter()
+                .map(|group| Ok((group[0].0[0], fold_level(1, group, tensor.side_length, discriminant)?)))
+                .collect::<Result<Vec<_>, String>>()?;
+            let root = build_tree(0, leaves, tensor.side_length, discriminant)?;
+            Ok(root.unwrap_or_else(|| AffineTuple::identity(discriminant)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::consistency::verify_consistency;
+    use crate::topology::testing::{discriminant, register};
+
+    /// 增量聚合索引在逐批注册与撤销之后，与全量重建及并行折叠的结果一致
+    #[test]
+    fn incremental_root_matches_a_full_refold() {
+        let d = discriminant();
+        let mut tensor = HyperTensor::new(3, 8, d.clone());
+        let folder = ParallelFolder::new(2).unwrap();
+        let ids: Vec<String> = (0..24).map(|i| format!("user-{}", i)).collect();
+
+        let check = |tensor: &mut HyperTensor| {
+            let root = tensor.calculate_global_root().unwrap();
+            let rebuilt = tensor.compute_root_internal().unwrap();
+            let parallel = folder.fold(tensor).unwrap();
+            for other in [&rebuilt, &parallel] {
+                assert_eq!(other.p_factor, root.p_factor);
+                assert_eq!(other.q_shift, root.q_shift);
+            }
+        };
 
-        if current_dim == self.dimensions {
-             return Ok(AffineTuple::identity(&self.discriminant));
+        for chunk in ids.chunks(8) {
+            for id in chunk {
+                register(&mut tensor, id).unwrap();
+            }
+            tensor.commit_epoch().unwrap().expect("pending changes");
+            check(&mut tensor);
         }
+        for id in ids.iter().step_by(5) {
+            assert!(tensor.revoke(id).unwrap());
+        }
+        tensor.commit_epoch().unwrap().expect("pending changes");
+        check(&mut tensor);
+    }
 
-        // 按当前维度的索引分组 O(N)
-        let mut groups: HashMap<usize, HashMap<Vec<usize>, AffineTuple>> = HashMap::new();
-        for (coord, tuple) in relevant_data {
-            if current_dim >= coord.len() { continue; }
-            let idx = coord[current_dim];
-            groups.entry(idx)
-                .or_insert_with(HashMap::new)
-                .insert(coord.clone(), tuple.clone());
+    /// 远超 4096 / |P| 个成员时根的 P 仍为定长哈希，见证与一致性证明照常重建
+    #[test]
+    fn root_stays_bounded_past_the_p_factor_halt() {
+        let d = discriminant();
+        let mut tensor = HyperTensor::new(4, 16, d.clone());
+        let members = 8 * (4096 / tensor.prime_config.bit_size) as usize;
+        let ids: Vec<String> = (0..members).map(|i| format!("user-{}", i)).collect();
+
+        for chunk in ids.chunks(members / 8) {
+            for id in chunk {
+                register(&mut tensor, id).unwrap();
+            }
+            tensor.commit_epoch().unwrap().expect("pending changes");
         }
+        let root = tensor.calculate_global_root().unwrap();
+        assert!(root.p_factor.significant_bits() <= 256);
 
-        let mut layer_agg = AffineTuple::identity(&self.discriminant);
-        let mut sorted_indices: Vec<usize> = groups.keys().cloned().collect();
-        sorted_indices.sort(); 
+        let rebuilt = tensor.compute_root_internal().unwrap();
+        let parallel = ParallelFolder::new(2).unwrap().fold(&tensor).unwrap();
+        for other in [&rebuilt, &parallel] {
+            assert_eq!(other.p_factor, root.p_factor);
+            assert_eq!(other.q_shift, root.q_shift);
+        }
 
-        for idx in sorted_indices {
-            let sub_map = groups.get(&idx).unwrap();
-            let sub_result = self.fold_sparse(current_dim + 1, sub_map)?;
-            layer_agg = layer_agg.compose(&sub_result, &self.discriminant)?;
+        for id in ids.iter().step_by(37) {
+            let witness = tensor.membership_witness(id).unwrap().expect("member");
+            assert_eq!(witness.compute_root(&d).unwrap().p_factor, root.p_factor);
+            let path = tensor.path_aggregates(&witness.coord);
+            assert_eq!(path.len(), tensor.dimensions + 1);
+            assert_eq!(path.last().unwrap().p_factor, root.p_factor);
         }
 
-        Ok(layer_agg)
+        let proof = tensor.consistency_proof(0, tensor.epoch).unwrap();
+        let report = verify_consistency(&AffineTuple::identity(&d), &root, &proof, &d).unwrap();
+        assert_eq!(report.appended, members as u64);
+        assert!(report.is_append_only());
     }
 }
@@ -0,0 +1,75 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use crate::core::algebra::ClassGroupElement;
+use rug::Integer;
+use serde::{Serialize, Deserialize};
+
+/// 单元格 (Bucket) 内的一名成员
+/// 合成不满足交换律，单元格的值取决于成员顺序，因此按到达顺序显式记录 depth，
+/// 重放时按 depth 依次合成即可得到逐位一致的单元格值。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellMember {
+    /// 用户 ID 摘要 (不落盘明文 ID)
+    pub id_digest: [u8; 32],
+    /// 桶内位置 (0 为最早到达者)
+    pub depth: u32,
+    pub prime: Integer,
+    /// 注册时 Hash-to-Prime 命中的 nonce，证明直接返回而无需重新搜索
+    pub nonce: u64,
+    pub q_shift: ClassGroupElement,
+}
+
+impl CellMember {
+    pub fn tuple(&self) -> AffineTuple {
+        AffineTuple {
+            p_factor: self.prime.clone(),
+            q_shift: self.q_shift.clone(),
+        }
+    }
+}
+
+/// 重复注册策略
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RegistrationPolicy {
+    /// 已注册的 ID 不再合成，返回 AlreadyRegistered (幂等)
+    #[default]
+    RejectDuplicates,
+    /// 显式重新注册：移除旧记录，以新元组追加到桶尾
+    Reregister,
+}
+
+/// insert 的结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertOutcome {
+    Inserted { depth: u32 },
+    AlreadyRegistered { depth: u32 },
+    Reregistered { depth: u32 },
+}
+
+/// 单次批量注册的上限 (与请求大小限制相匹配)
+pub const MAX_BATCH_SIZE: usize = 10_000;
+
+/// 成员 ID 摘要：带域分离与长度前缀
+pub fn member_digest(user_id: &str) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"htp:member-id:v1");
+    hasher.update(&(user_id.len() as u64).to_le_bytes());
+    hasher.update(user_id.as_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// 按 depth 顺序重放成员列表，得到单元格值；空列表返回 None
+pub fn fold_members(members: &[CellMember], discriminant: &Integer) -> Result<Option<AffineTuple>, String> {
+    let mut acc: Option<AffineTuple> = None;
+    for (i, m) in members.iter().enumerate() {
+        if m.depth as usize != i {
+            return Err(format!("Membership list corrupted: expected depth {}, found {}.", i, m.depth));
+        }
+        acc = Some(match acc {
+            Some(prev) => prev.compose(&m.tuple(), discriminant)?,
+            None => m.tuple(),
+        });
+    }
+    Ok(acc)
+}
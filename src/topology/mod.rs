@@ -1,2 +1,12 @@
 pub mod tensor;
 pub mod folding;
+pub mod segment_tree;
+pub mod membership;
+pub mod epoch;
+pub mod consistency;
+pub mod witness;
+pub mod snapshot;
+pub mod reshard;
+pub mod placement;
+#[cfg(test)]
+pub mod testing;
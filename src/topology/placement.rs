@@ -0,0 +1,252 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::MAX_P_FACTOR_BITS;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Probe 策略下每个 ID 最多尝试的候选地址数
+pub const MAX_PROBES: u32 = 8;
+
+/// 单元格已满时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FullCellPolicy {
+    /// 拒绝注册
+    #[default]
+    Reject,
+    /// 沿该 ID 的探测序列改投下一个候选地址 (最多 MAX_PROBES 个)
+    Probe,
+}
+
+impl FullCellPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FullCellPolicy::Reject => "reject",
+            FullCellPolicy::Probe => "probe",
+        }
+    }
+}
+
+impl fmt::Display for FullCellPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for FullCellPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(FullCellPolicy::Reject),
+            "probe" => Ok(FullCellPolicy::Probe),
+            other => Err(format!("Unknown full-cell policy '{}'. Expected one of: reject, probe.", other)),
+        }
+    }
+}
+
+/// 成员寻址方式 (随张量持久化，创建后不可更改)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AddressingMode {
+    /// 字符串 ID 经 (带密钥的) 哈希映射到坐标
+    #[default]
+    Hashed,
+    /// 注册时依次分配逻辑序号 i，坐标 v_k = (i // L^{k-1}) mod L；每个单元格至多一名成员
+    Sequential,
+}
+
+impl AddressingMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AddressingMode::Hashed => "hashed",
+            AddressingMode::Sequential => "sequential",
+        }
+    }
+}
+
+impl fmt::Display for AddressingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for AddressingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hashed" | "hash" => Ok(AddressingMode::Hashed),
+            "sequential" | "numeric" => Ok(AddressingMode::Sequential),
+            other => Err(format!("Unknown addressing mode '{}'. Expected one of: hashed, sequential.", other)),
+        }
+    }
+}
+
+/// [SECURITY FIX]: 坐标映射与单元格占用策略 (随张量持久化)
+/// 公开的无密钥映射允许攻击者离线碾磨 ID 落入指定单元格并不断合成，直到 compose 触发 4096-bit 熔断 (Bucket Jamming)。
+/// 部署级密钥使映射不可预测；占用上限使单个单元格合成后的 P 不超过熔断阈值，满时按策略拒绝或改投。
+/// 不派生 Debug，避免密钥进入日志。
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placement {
+    /// blake3 keyed 模式的坐标密钥；None 为公开映射 (旧版张量)
+    pub coord_key: Option<[u8; 32]>,
+    /// 每个单元格的成员上限；0 表示按 P-factor 上限推导
+    pub max_cell_members: u32,
+    pub full_cell: FullCellPolicy,
+}
+
+impl Placement {
+    /// 使用操作系统随机源生成新的坐标密钥
+    pub fn keyed(max_cell_members: u32, full_cell: FullCellPolicy) -> Result<Self, String> {
+        let mut key = [0u8; 32];
+        getrandom::getrandom(&mut key).map_err(|e| format!("Failed to generate coordinate key: {}", e))?;
+        Ok(Placement { coord_key: Some(key), max_cell_members, full_cell })
+    }
+
+    pub fn is_keyed(&self) -> bool {
+        self.coord_key.is_some()
+    }
+
+    /// 第 probe 个候选地址 (128-bit，与几何无关)；无密钥时 probe 0 与旧版映射一致
+    pub fn address(&self, user_id: &str, probe: u32) -> u128 {
+        let mut hasher = match &self.coord_key {
+            Some(key) => blake3::Hasher::new_keyed(key),
+            None => blake3::Hasher::new(),
+        };
+        hasher.update(user_id.as_bytes());
+        hasher.update(b":htp:coord:v2");
+        if probe > 0 {
+            hasher.update(&probe.to_le_bytes());
+        }
+        let hash_output = hasher.finalize();
+        // [SECURITY FIX]: 扩大寻址空间防止 Bucket Jamming (存储桶堵塞)
+        // 使用整个 128-bit (或更多) 来决定坐标，极大降低人为构造碰撞的风险
+        u128::from_le_bytes(hash_output.as_bytes()[0..16].try_into().unwrap())
+    }
+
+    /// [SECURITY FIX]: 顺序寻址下非成员的假序号，均匀落在已分配区间 0..next_index 内 (区间为空时为 0)。
+    /// 以坐标密钥派生，客户端无法复算，假证明的序号与真实证明同分布；无密钥的旧张量退化为公开派生。
+    pub fn dummy_index(&self, user_id: &str, next_index: u64) -> u64 {
+        let mut hasher = match &self.coord_key {
+            Some(key) => blake3::Hasher::new_keyed(key),
+            None => blake3::Hasher::new(),
+        };
+        hasher.update(user_id.as_bytes());
+        hasher.update(b":htp:dummy-index:v1");
+        let value = u128::from_le_bytes(hasher.finalize().as_bytes()[0..16].try_into().unwrap());
+        (value % next_index.max(1) as u128) as u64
+    }
+
+    /// 候选地址个数
+    pub fn probes(&self) -> u32 {
+        match self.full_cell {
+            FullCellPolicy::Reject => 1,
+            FullCellPolicy::Probe => MAX_PROBES,
+        }
+    }
+
+    /// 实际生效的单元格成员上限：不超过 P-factor 上限可容纳的 prime_bits 位素数个数
+    pub fn cell_limit(&self, prime_bits: u32) -> usize {
+        let ceiling = (MAX_P_FACTOR_BITS / prime_bits.max(1)).max(1);
+        match self.max_cell_members {
+            0 => ceiling as usize,
+            n => n.min(ceiling) as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::membership::member_digest;
+    use crate::topology::tensor::HyperTensor;
+    use crate::topology::testing::{discriminant, register};
+
+    /// 两个单元格、每格至多一名成员的一维张量，使用公开映射以便测试可复现
+    fn tiny(full_cell: FullCellPolicy) -> HyperTensor {
+        let mut tensor = HyperTensor::new(1, 2, discriminant());
+        tensor.placement = Placement { coord_key: None, max_cell_members: 1, full_cell };
+        tensor
+    }
+
+    /// 首个候选地址与 owner 落在同一单元格的 ID
+    fn colliding(tensor: &HyperTensor, owner: &str) -> String {
+        let cell = tensor.map_id_to_coord_hash(owner);
+        (0..).map(|i| format!("guest-{}", i))
+            .find(|id| tensor.map_id_to_coord_hash(id) == cell)
+            .unwrap()
+    }
+
+    #[test]
+    fn keyed_mapping_depends_on_the_key() {
+        let public = Placement::default();
+        let digest = blake3::hash(b"alice:htp:coord:v2");
+        assert_eq!(public.address("alice", 0), u128::from_le_bytes(digest.as_bytes()[..16].try_into().unwrap()));
+        assert_ne!(public.address("alice", 0), public.address("alice", 1));
+
+        let keyed = Placement::keyed(0, FullCellPolicy::Reject).unwrap();
+        let other = Placement::keyed(0, FullCellPolicy::Reject).unwrap();
+        assert!(keyed.is_keyed() && !public.is_keyed());
+        assert_eq!(keyed.address("alice", 0), keyed.clone().address("alice", 0));
+        assert_ne!(keyed.address("alice", 0), public.address("alice", 0));
+        assert_ne!(keyed.address("alice", 0), other.address("alice", 0));
+    }
+
+    #[test]
+    fn reject_refuses_a_full_cell() {
+        let mut tensor = tiny(FullCellPolicy::Reject);
+        assert_eq!(tensor.placement.probes(), 1);
+        register(&mut tensor, "alice").unwrap();
+        let guest = colliding(&tensor, "alice");
+        let err = register(&mut tensor, &guest).unwrap_err();
+        assert!(err.contains("Cell full"), "{}", err);
+        assert!(!tensor.is_registered(&guest).unwrap());
+        assert!(!tensor.addresses.contains_key(&member_digest(&guest)));
+    }
+
+    #[test]
+    fn probe_moves_to_the_next_candidate() {
+        let mut tensor = tiny(FullCellPolicy::Probe);
+        assert_eq!(tensor.placement.probes(), MAX_PROBES);
+        register(&mut tensor, "alice").unwrap();
+        let guest = colliding(&tensor, "alice");
+        register(&mut tensor, &guest).unwrap();
+
+        // 改投的地址随成员记录，之后按该地址定位
+        let address = tensor.addresses[&member_digest(&guest)];
+        assert!((1..MAX_PROBES).any(|probe| tensor.placement.address(&guest, probe) == address));
+        assert_ne!(tensor.map_id_to_coord_hash(&guest), tensor.map_id_to_coord_hash("alice"));
+        assert!(tensor.is_registered(&guest).unwrap());
+    }
+
+    #[test]
+    fn probing_gives_up_after_max_probes() {
+        let mut tensor = tiny(FullCellPolicy::Probe);
+        let mut registered = 0;
+        for i in 0.. {
+            if register(&mut tensor, &format!("user-{}", i)).is_ok() {
+                registered += 1;
+            }
+            if registered == 2 {
+                break;
+            }
+        }
+        // 两个单元格均已满：所有 MAX_PROBES 个候选都被拒绝
+        let err = register(&mut tensor, "late").unwrap_err();
+        assert!(err.contains("Cell full"), "{}", err);
+        assert_eq!(tensor.data.len(), 2);
+        assert!(!tensor.addresses.contains_key(&member_digest("late")));
+    }
+
+    #[test]
+    fn cell_limit_is_capped_by_the_p_factor() {
+        let limit = |max_cell_members, prime_bits| Placement { max_cell_members, ..Placement::default() }.cell_limit(prime_bits);
+        assert_eq!(limit(0, 128), (MAX_P_FACTOR_BITS / 128) as usize);
+        assert_eq!(limit(8, 128), 8);
+        assert_eq!(limit(u32::MAX, 128), (MAX_P_FACTOR_BITS / 128) as usize);
+        assert_eq!(limit(0, 256), (MAX_P_FACTOR_BITS / 256) as usize);
+        // 超大素数也至少容纳一名成员；位长 0 不会除零
+        assert_eq!(limit(0, MAX_P_FACTOR_BITS * 2), 1);
+        assert_eq!(limit(0, 0), MAX_P_FACTOR_BITS as usize);
+    }
+}
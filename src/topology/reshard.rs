@@ -0,0 +1,242 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use super::tensor::{HyperTensor, PackedCoord};
+use super::placement::AddressingMode;
+use super::epoch::{unix_now, EpochRecord};
+use super::membership::{CellMember, RegistrationPolicy};
+use crate::storage::store::{CellRecord, CellStore};
+use crate::storage::wal::WalOp;
+
+/// [NEW FEATURE]: 在线重分片 (改变维度或边长)
+/// 1. 写锁内冻结当前张量并开启 reshard_tap，之后的 WAL 操作同时记录一份；
+/// 2. 锁外以冻结快照为输入，按规范的成员日志 (旧单元格键升序、桶内 depth 顺序) 把每个成员按其地址落入新几何，
+///    期间旧张量照常服务证明与注册；
+/// 3. 切换时 (写锁内) 在新几何上追补 tap 中的操作，沿用旧的根历史并以新 epoch 提交新根，整体替换旧张量。
+///
+/// 单元格布局整体变化，跨越切换 epoch 的一致性证明与见证更新无法表达，客户端需重新获取证明。
+impl HyperTensor {
+    /// 开始重分片：返回作为重建输入的冻结快照
+    pub fn begin_reshard(&mut self) -> Result<HyperTensor, String> {
+        if self.reshard_tap.is_some() {
+            return Err("Re-sharding already in progress.".to_string());
+        }
+        let frozen = self.freeze()?;
+        self.reshard_tap = Some(Vec::new());
+        Ok(frozen)
+    }
+
+    pub fn abort_reshard(&mut self) {
+        self.reshard_tap = None;
+    }
+
+    /// 以新几何重建全部单元格 (在冻结快照上调用；耗时与成员数成正比)
+    pub fn reshard_into(&self, dim: usize, len: usize, store: CellStore) -> Result<HyperTensor, String> {
+        Self::check_geometry(dim, len)?;
+        if (dim, len) == (self.dimensions, self.side_length) {
+            return Err(format!("Tensor already uses geometry {}^{}.", len, dim));
+        }
+        let mut next = HyperTensor::new_with_store(dim, len, self.discriminant.clone(), self.prime_config.clone(), store);
+        next.history_limit = self.history_limit;
+        next.addresses = self.addresses.clone();
+        next.placement = self.placement.clone();
+        next.addressing = self.addressing;
+        next.next_index = self.next_index;
+
+        for entry in self.data.range(self.full_range()) {
+            let (_, cell) = entry?;
+            for member in &cell.members {
+                next.place(member.clone())?;
+            }
+        }
+        Ok(next)
+    }
+
+    /// 按地址追加到新几何中对应单元格的桶尾
+    fn place(&mut self, member: CellMember) -> Result<(), String> {
+        if self.data.capacity().is_some_and(|max| self.data.len() > max) {
+            return Err("Server Capacity Reached".to_string());
+        }
+        let key = self.cell_of(&member.id_digest)?;
+        let limit = self.placement.cell_limit(self.prime_config.bit_size);
+        let (value, mut members) = match self.data.get(key)? {
+            Some(existing) if existing.members.len() >= limit => {
+                return Err(format!("Target geometry {}^{} is too small: a cell exceeds the limit of {} members.", self.side_length, self.dimensions, limit));
+            },
+            Some(existing) => {
+                let existing = existing.into_owned();
+                (existing.value.compose(&member.tuple(), &self.discriminant)?, existing.members)
+            },
+            None => (member.tuple(), Vec::new()),
+        };
+        let depth = members.len() as u32;
+        members.push(CellMember { depth, ..member });
+        self.data.put(key, CellRecord { value, members })?;
+        self.dirty.insert(key);
+        Ok(())
+    }
+
+    /// 成员在本几何中的单元格；顺序寻址的序号必须落在 L^d 之内 (不能取模折回)
+    fn cell_of(&self, id_digest: &[u8; 32]) -> Result<PackedCoord, String> {
+        let address = self.addresses.get(id_digest).copied().ok_or_else(|| {
+            "Member has no recorded coordinate address (registered before address tracking); re-register it before re-sharding.".to_string()
+        })?;
+        if self.addressing == AddressingMode::Sequential && Self::cell_capacity(self.dimensions, self.side_length).is_some_and(|max| address >= max) {
+            return Err(format!("Target geometry {}^{} cannot hold sequential index {}.", self.side_length, self.dimensions, address));
+        }
+        Ok(self.pack(&self.coord_from_address(address)))
+    }
+
+    /// 追补冻结之后的操作：单元格键按地址在新几何上重新计算，epoch 边界在切换时统一提交
+    pub fn replay_remapped(&mut self, ops: Vec<WalOp>) -> Result<(), String> {
+        for op in ops {
+            match op {
+                WalOp::Address { id_digest, address } => {
+                    self.note_address(id_digest, address);
+                },
+                WalOp::Insert { member, replace, .. } => {
+                    let key = self.cell_of(&member.id_digest)?;
+                    let policy = if replace { RegistrationPolicy::Reregister } else { RegistrationPolicy::RejectDuplicates };
+                    self.insert_member(key, member, policy)?;
+                },
+                WalOp::Revoke { id_digest, .. } => {
+                    let key = self.cell_of(&id_digest)?;
+                    self.revoke_member(key, id_digest)?;
+                },
+                WalOp::Commit { .. } => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// 切换：沿用 live 的根历史，以 live.epoch + 1 提交新几何的根
+    pub fn complete_reshard(&mut self, live: &HyperTensor) -> Result<EpochRecord, String> {
+        if live.pending_changes > 0 {
+            return Err("Cannot switch geometry with uncommitted changes.".to_string());
+        }
+        let root = self.calculate_global_root()?;
+        self.epoch = live.epoch + 1;
+        let record = EpochRecord {
+            epoch: self.epoch,
+            root,
+            timestamp: unix_now(),
+            changes: self.addresses.len() as u64,
+        };
+        self.history = live.history.clone();
+        self.history.push_back(record.clone());
+        while self.history.len() > self.history_limit.max(1) {
+            self.history.pop_front();
+        }
+        self.pending_changes = 0;
+        self.pending_cells.clear();
+        self.journal.clear();
+        self.witness_updates.clear();
+        self.wal_buffer.clear();
+        self.wal_seq = live.wal_seq;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::affine::AffineTuple;
+    use crate::topology::tensor::index_to_coord;
+    use crate::topology::testing::{discriminant, register};
+
+    const USERS: usize = 16;
+
+    fn same(x: &AffineTuple, y: &AffineTuple) -> bool {
+        x.p_factor == y.p_factor && x.q_shift == y.q_shift
+    }
+
+    fn user(i: usize) -> String {
+        format!("user-{}", i)
+    }
+
+    /// 冻结时已有 12 人；重建期间旧张量继续注册 4 人并撤销 user-3 与 user-12，之后切换到新几何
+    fn reshard_with_writes_in_flight(addressing: AddressingMode, from: (usize, usize), to: (usize, usize)) -> (HyperTensor, HyperTensor, EpochRecord) {
+        let mut live = HyperTensor::new(from.0, from.1, discriminant());
+        live.addressing = addressing;
+        for i in 0..12 {
+            register(&mut live, &user(i)).unwrap();
+        }
+        live.commit_epoch().unwrap().unwrap();
+        live.take_wal_ops();
+
+        let frozen = live.begin_reshard().unwrap();
+        assert!(live.begin_reshard().is_err());
+        let mut next = frozen.reshard_into(to.0, to.1, CellStore::default()).unwrap();
+
+        for i in 12..USERS {
+            register(&mut live, &user(i)).unwrap();
+        }
+        assert!(live.revoke("user-3").unwrap());
+        live.commit_epoch().unwrap().unwrap();
+        live.take_wal_ops();
+        assert!(live.revoke("user-12").unwrap());
+        live.commit_epoch().unwrap().unwrap();
+        live.take_wal_ops();
+
+        next.replay_remapped(live.reshard_tap.take().unwrap()).unwrap();
+        let record = next.complete_reshard(&live).unwrap();
+        (live, next, record)
+    }
+
+    fn assert_switched(live: &HyperTensor, next: &mut HyperTensor, record: &EpochRecord) {
+        // 成员集合不变
+        for i in 0..USERS {
+            assert_eq!(next.is_registered(&user(i)).unwrap(), live.is_registered(&user(i)).unwrap(), "{}", user(i));
+        }
+        assert!(!next.is_registered("user-3").unwrap() && !next.is_registered("user-12").unwrap());
+        assert_eq!(next.addresses.len(), USERS - 2);
+
+        // epoch 恰好推进一次，历史在切换处连续
+        assert_eq!((record.epoch, next.epoch), (live.epoch + 1, live.epoch + 1));
+        assert_eq!(next.history.iter().map(|r| r.epoch).collect::<Vec<_>>(), (0..=next.epoch).collect::<Vec<_>>());
+        assert!(same(&next.root_at(live.epoch).unwrap().root, &live.root_at(live.epoch).unwrap().root));
+        assert_eq!(next.pending_changes, 0);
+
+        // 每个成员的见证都复现新根
+        let root = next.calculate_global_root().unwrap();
+        assert!(same(&root, &record.root));
+        for i in (0..USERS).filter(|&i| i != 3 && i != 12) {
+            let witness = next.membership_witness(&user(i)).unwrap().unwrap();
+            assert!(same(&witness.compute_root(&next.discriminant).unwrap(), &root), "{}", user(i));
+        }
+    }
+
+    #[test]
+    fn hashed_reshard_keeps_members_and_witnesses() {
+        let (live, mut next, record) = reshard_with_writes_in_flight(AddressingMode::Hashed, (3, 4), (2, 9));
+        assert_switched(&live, &mut next, &record);
+        assert!(next.sequential_index("user-0").is_none());
+    }
+
+    #[test]
+    fn sequential_indices_survive_a_new_side_length() {
+        let (live, mut next, record) = reshard_with_writes_in_flight(AddressingMode::Sequential, (2, 5), (3, 3));
+        assert_switched(&live, &mut next, &record);
+        assert_eq!(next.next_index, live.next_index);
+        for i in (0..USERS).filter(|&i| i != 3 && i != 12) {
+            let index = live.sequential_index(&user(i)).unwrap();
+            assert_eq!(next.sequential_index(&user(i)), Some(index), "{}", user(i));
+            // 新几何按同一序号复算坐标 (L 从 5 变为 3)
+            assert_eq!(next.membership_witness(&user(i)).unwrap().unwrap().coord, index_to_coord(index, 3, 3));
+        }
+    }
+
+    #[test]
+    fn sequential_reshard_rejects_a_geometry_too_small_for_its_indices() {
+        let mut live = HyperTensor::new(2, 5, discriminant());
+        live.addressing = AddressingMode::Sequential;
+        for i in 0..12 {
+            register(&mut live, &user(i)).unwrap();
+        }
+        live.commit_epoch().unwrap().unwrap();
+        let frozen = live.begin_reshard().unwrap();
+        assert!(frozen.reshard_into(2, 3, CellStore::default()).is_err());
+        assert!(frozen.reshard_into(2, 5, CellStore::default()).is_err());
+        live.abort_reshard();
+        assert!(live.reshard_tap.is_none());
+    }
+}
@@ -0,0 +1,126 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use rug::{Integer, integer::Order};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+/// 稀疏聚合线段树 (1D 聚合)
+/// 采用隐式堆下标：根为 1，叶子 i 位于 `width + i`。缺失节点表示空子树 (不存储)。
+/// 叶子为单元格值或下一维的子树根；内部节点由 merge_nodes 计算。
+#[derive(Clone, Debug)]
+pub struct SparseSegmentTree {
+    /// 本树所在的维度 (参与节点摘要的域分离)
+    level: usize,
+    width: usize,
+    nodes: HashMap<usize, AffineTuple>,
+}
+
+/// 有序合成两个可选元组 (单元格内的成员链)；单位元一侧直接跳过，避免无意义的 pow 运算
+pub fn combine(left: Option<&AffineTuple>, right: Option<&AffineTuple>, discriminant: &Integer) -> Result<Option<AffineTuple>, String> {
+    match (left, right) {
+        (None, None) => Ok(None),
+        (Some(l), None) => Ok(Some(l.clone())),
+        (None, Some(r)) => Ok(Some(r.clone())),
+        (Some(l), Some(r)) => Ok(Some(l.compose(r, discriminant)?)),
+    }
+}
+
+/// [SECURITY FIX]: 索引节点的合并 (第 level 维、堆下标 pos 处的父节点)
+/// 仿射合成会把子树内全部素数乘进 P-factor，成员数超过 4096 / prime_bits 时根即触发熔断。
+/// 单元格之上改为有界的有序承诺：P 为 (level, pos, 左, 右) 的 blake3 摘要，绑定子节点顺序与位置；
+/// Q 为两侧 Q 的类群乘积。节点大小与成员数无关，两侧均为空时才是空节点。
+pub fn merge_nodes(level: usize, pos: usize, left: Option<&AffineTuple>, right: Option<&AffineTuple>, discriminant: &Integer) -> Result<Option<AffineTuple>, String> {
+    let q_shift = match (left, right) {
+        (None, None) => return Ok(None),
+        (Some(l), None) => l.q_shift.clone(),
+        (None, Some(r)) => r.q_shift.clone(),
+        (Some(l), Some(r)) => l.q_shift.compose(&r.q_shift, discriminant)?,
+    };
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"htp:aggregate:v1");
+    hasher.update(&(level as u64).to_le_bytes());
+    hasher.update(&(pos as u64).to_le_bytes());
+    absorb_child(&mut hasher, left);
+    absorb_child(&mut hasher, right);
+    let p_factor = Integer::from_digits(hasher.finalize().as_bytes(), Order::Lsf);
+    Ok(Some(AffineTuple { p_factor, q_shift }))
+}
+
+fn absorb_child(hasher: &mut blake3::Hasher, child: Option<&AffineTuple>) {
+    let Some(t) = child else {
+        hasher.update(&[0]);
+        return;
+    };
+    hasher.update(&[1]);
+    for n in [&t.p_factor, &t.q_shift.a, &t.q_shift.b, &t.q_shift.c] {
+        let digits = n.to_digits::<u8>(Order::Lsf);
+        hasher.update(&[(n.cmp0() == Ordering::Less) as u8]);
+        hasher.update(&(digits.len() as u64).to_le_bytes());
+        hasher.update(&digits);
+    }
+}
+
+impl SparseSegmentTree {
+    pub fn new(level: usize, len: usize) -> Self {
+        SparseSegmentTree {
+            level,
+            width: len.max(1).next_power_of_two(),
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// 整段聚合；None 表示单位元
+    pub fn root(&self) -> Option<&AffineTuple> {
+        self.nodes.get(&1)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// 堆下标处的节点 (1 为根)
+    pub fn node(&self, pos: usize) -> Option<&AffineTuple> {
+        self.nodes.get(&pos)
+    }
+
+    pub fn leaf(&self, idx: usize) -> Option<&AffineTuple> {
+        self.nodes.get(&(self.width + idx))
+    }
+
+    /// 批量更新叶子 (None 表示清空)，只重算受影响的祖先节点
+    /// 每个叶子 O(log L) 次合成，共享祖先只计算一次
+    pub fn update(&mut self, updates: Vec<(usize, Option<AffineTuple>)>, discriminant: &Integer) -> Result<(), String> {
+        let mut parents = BTreeSet::new();
+        for (idx, value) in updates {
+            if idx >= self.width {
+                return Err(format!("Segment tree index {} out of range ({}).", idx, self.width));
+            }
+            let pos = self.width + idx;
+            match value {
+                Some(t) => { self.nodes.insert(pos, t); },
+                None => { self.nodes.remove(&pos); },
+            }
+            parents.insert(pos >> 1);
+        }
+
+        // 自底向上逐层重算：同层节点下标位于同一区间，按降序处理即保证子节点先于父节点
+        while let Some(pos) = parents.pop_last() {
+            if pos == 0 {
+                continue;
+            }
+            let merged = merge_nodes(self.level, pos, self.nodes.get(&(2 * pos)), self.nodes.get(&(2 * pos + 1)), discriminant)?;
+            match merged {
+                Some(t) => { self.nodes.insert(pos, t); },
+                None => { self.nodes.remove(&pos); },
+            }
+            parents.insert(pos >> 1);
+        }
+        Ok(())
+    }
+}
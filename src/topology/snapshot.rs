@@ -0,0 +1,49 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use super::tensor::HyperTensor;
+use crate::core::affine::AffineTuple;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+
+/// [NEW FEATURE]: 按 epoch 发布的不可变张量快照
+/// 写者提交 epoch 后冻结并原子替换；读者 (GetProof / GetGlobalRoot) 无锁加载最新快照，
+/// 持有的快照在替换后依然有效，因此证明生成永远不会等待注册与折叠。
+pub struct EpochSnapshots {
+    current: ArcSwap<HyperTensor>,
+}
+
+impl EpochSnapshots {
+    pub fn new(tensor: &mut HyperTensor) -> Result<Self, String> {
+        Ok(EpochSnapshots { current: ArcSwap::from_pointee(tensor.freeze()?) })
+    }
+
+    /// 最新已发布的快照
+    pub fn load(&self) -> Arc<HyperTensor> {
+        self.current.load_full()
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.current.load().epoch
+    }
+
+    /// 快照对应 epoch 的全局根
+    pub fn root(&self) -> Result<AffineTuple, String> {
+        self.current.load().cached_root.clone().ok_or_else(|| "Snapshot published without a root.".to_string())
+    }
+
+    /// 张量已推进到新 epoch 时发布新快照；epoch 未变化时不做任何事
+    pub fn publish(&self, tensor: &mut HyperTensor) -> Result<(), String> {
+        if tensor.epoch == self.epoch() {
+            return Ok(());
+        }
+        self.install(tensor.freeze()?);
+        Ok(())
+    }
+
+    /// 发布已冻结的快照；不会回退到更早的 epoch
+    pub fn install(&self, frozen: HyperTensor) {
+        if frozen.epoch > self.epoch() {
+            self.current.store(Arc::new(frozen));
+        }
+    }
+}
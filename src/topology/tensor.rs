@@ -1,61 +1,276 @@
 // COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::Range;
 use rug::Integer;
 use crate::core::affine::AffineTuple;
-use blake3;
+use crate::core::primes::{PrimeHashConfig, PrimeRepresentative};
+use super::folding::AggregateIndex;
+use super::epoch::{unix_now, EpochRecord, DEFAULT_ROOT_HISTORY};
+use super::consistency::{CellTransition, ConsistencyProof, EpochDelta};
+use super::witness::EpochUpdate;
+use super::membership::{member_digest, fold_members, CellMember, InsertOutcome, RegistrationPolicy, MAX_BATCH_SIZE};
+use super::placement::{AddressingMode, Placement};
+use super::segment_tree::combine;
+use crate::storage::wal::WalOp;
+use crate::core::algebra::ClassGroupElement;
 use serde::{Serialize, Deserialize};
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use crate::storage::{format, write_atomic};
+use crate::storage::store::{CellRecord, CellStore};
 
 pub type Coordinate = Vec<usize>;
+/// 成员 ID 摘要 → 128-bit 坐标地址 (持久化结构，冻结快照时 O(1) 共享)
+pub type MemberAddresses = im::HashMap<[u8; 32], u128>;
+/// 成员 ID 摘要 → 素数代表元 (批量注册时在写锁之外生成)
+pub type BatchPrimes = BTreeMap<[u8; 32], PrimeRepresentative>;
+
+/// 逻辑序号 → 坐标：v_k = (i // L^{k-1}) mod L (SPECIFICATION §3.1)；验证方据证明中的序号复算坐标
+pub fn index_to_coord(index: u64, dimensions: usize, side_length: usize) -> Coordinate {
+    let mut coord = Vec::with_capacity(dimensions);
+    let mut temp = index;
+    let l = side_length as u64;
+    for _ in 0..dimensions {
+        coord.push((temp % l) as usize);
+        temp /= l;
+    }
+    coord
+}
+
+/// 批量注册的素数阶段：为批内不重复的 ID 并行生成代表元。不访问张量状态，可在任何锁之外执行
+pub fn batch_primes<S: AsRef<str>>(config: &PrimeHashConfig, ids: &[S]) -> Result<BatchPrimes, String> {
+    if ids.len() > MAX_BATCH_SIZE {
+        return Err(format!("Batch too large: {} users (max {}).", ids.len(), MAX_BATCH_SIZE));
+    }
+    let mut unique: BTreeMap<[u8; 32], &str> = BTreeMap::new();
+    for id in ids {
+        unique.entry(member_digest(id.as_ref())).or_insert(id.as_ref());
+    }
+    let names: Vec<&str> = unique.values().copied().collect();
+    let reps = config.hash_to_primes_parallel(&names)
+        .into_iter()
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(unique.into_keys().zip(reps).collect())
+}
+
+/// [FIX]: 未提交批次的撤销记录 - 各单元格与地址在本批次首次变更前的值。
+/// 提交前失败时据此回滚，内存中的张量不会领先于 WAL。
+#[derive(Clone, Default)]
+pub struct UndoLog {
+    active: bool,
+    cells: BTreeMap<PackedCoord, Option<CellRecord>>,
+    addresses: BTreeMap<[u8; 32], Option<u128>>,
+    next_index: u64,
+    wal_len: usize,
+}
+
+/// 定长打包坐标：混合进制 Σ c_i · L^(d-1-i)，第 0 维为最高位，
+/// 因此数值序即坐标字典序，同一前缀的单元格在存储中连续
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PackedCoord(pub u128);
 
 #[derive(Serialize, Deserialize)]
 pub struct HyperTensor {
     pub dimensions: usize,
     pub side_length: usize,
     pub discriminant: Integer,
-    pub data: HashMap<Coordinate, AffineTuple>,
+    // [NEW]: Hash-to-Prime 参数随张量持久化，保证注册与验证使用同一派生方式
+    pub prime_config: PrimeHashConfig,
+    // [PERF FIX]: 按打包坐标有序存储，折叠为单次顺序扫描，无需克隆键与大整数
+    // [NEW FEATURE]: 可插拔存储后端 (内存 / 磁盘日志)；每个单元格记录聚合值与有序成员列表，碰撞时可逐位重放
+    pub data: CellStore,
+    // [NEW]: Epoch 模型 - 每提交一批变更递增一次，客户端据此判断证明的新鲜度
+    pub epoch: u64,
+    /// 自上次提交以来尚未纳入 epoch 的变更数
+    pub pending_changes: u64,
+    /// 有界的不可变根历史 (按 epoch 升序)
+    pub history: VecDeque<EpochRecord>,
+    pub history_limit: usize,
+    // [NEW]: 本 epoch 内首次变更前各单元格的成员链快照，提交时生成一致性摘要
+    pub pending_cells: BTreeMap<PackedCoord, Vec<AffineTuple>>,
+    /// 与 history 对齐的每 epoch 变更摘要 (一致性证明)
+    pub journal: VecDeque<EpochDelta>,
+    /// 已写入快照的最后一条 WAL 序号，恢复时只重放其后的记录
+    pub wal_seq: u64,
+    #[serde(skip)]
+    pub wal_buffer: Vec<WalOp>,
+    /// 与 history 对齐的每 epoch 见证更新数据
+    pub witness_updates: VecDeque<EpochUpdate>,
+    // [NEW]: 成员 ID 摘要 → 128-bit 坐标地址；不落盘明文 ID，重分片时按新几何重新取模
+    pub addresses: MemberAddresses,
+    // [SECURITY FIX]: 坐标密钥与单元格占用策略
+    pub placement: Placement,
+    // [NEW FEATURE]: 数字 ID 模式 - 顺序寻址时记录下一个待分配的逻辑序号
+    pub addressing: AddressingMode,
+    pub next_index: u64,
+    /// 重分片期间记录新产生的 WAL 操作，切换时在新几何上追补
+    #[serde(skip)]
+    pub reshard_tap: Option<Vec<WalOp>>,
     #[serde(skip)]
     pub cached_root: Option<AffineTuple>, 
+    // [PERF FIX]: 分维度增量聚合，insert 只标记脏路径，不再触发全量折叠
+    #[serde(skip)]
+    pub aggregates: AggregateIndex,
+    #[serde(skip)]
+    pub dirty: BTreeSet<PackedCoord>,
+    #[serde(skip)]
+    pub undo: UndoLog,
 }
 
 impl HyperTensor {
     pub fn new(dim: usize, len: usize, discriminant: Integer) -> Self {
+        Self::new_with_config(dim, len, discriminant, PrimeHashConfig::default())
+    }
+
+    pub fn new_with_config(dim: usize, len: usize, discriminant: Integer, prime_config: PrimeHashConfig) -> Self {
+        Self::new_with_store(dim, len, discriminant, prime_config, CellStore::default())
+    }
+
+    /// 以指定存储后端创建空张量 (store 必须为空)
+    pub fn new_with_store(dim: usize, len: usize, discriminant: Integer, prime_config: PrimeHashConfig, store: CellStore) -> Self {
+        assert!(store.is_empty(), "Cell store must be empty for a new tensor.");
+        assert!(Self::cell_capacity(dim, len).is_some(), "Tensor geometry L^d = {}^{} exceeds the 128-bit coordinate space.", len, dim);
+        let genesis = EpochRecord {
+            epoch: 0,
+            root: AffineTuple::identity(&discriminant),
+            timestamp: unix_now(),
+            changes: 0,
+        };
         HyperTensor {
             dimensions: dim,
             side_length: len,
             discriminant,
-            data: HashMap::new(),
+            prime_config,
+            data: store,
+            epoch: 0,
+            pending_changes: 0,
+            history: VecDeque::from(vec![genesis]),
+            history_limit: DEFAULT_ROOT_HISTORY,
+            pending_cells: BTreeMap::new(),
+            journal: VecDeque::new(),
+            wal_seq: 0,
+            wal_buffer: Vec::new(),
+            witness_updates: VecDeque::new(),
+            addresses: MemberAddresses::new(),
+            placement: Placement::default(),
+            addressing: AddressingMode::default(),
+            next_index: 0,
+            reshard_tap: None,
             cached_root: None,
+            aggregates: AggregateIndex::default(),
+            dirty: BTreeSet::new(),
+            undo: UndoLog::default(),
         }
     }
 
-    pub fn map_id_to_coord(&self, numeric_id: u64) -> Coordinate {
-        let mut coord = Vec::with_capacity(self.dimensions);
-        let mut temp = numeric_id;
-        let l = self.side_length as u64;
-        for _ in 0..self.dimensions {
-            coord.push((temp % l) as usize);
-            temp /= l;
+    /// 单元格总数 L^d；超出 u128 时返回 None (坐标无法打包)
+    pub fn cell_capacity(dim: usize, len: usize) -> Option<u128> {
+        (len as u128).checked_pow(u32::try_from(dim).ok()?)
+    }
+
+    /// 几何合法性：至少一维、边长至少为 2，且 L^d 落在 128-bit 坐标空间内
+    pub fn check_geometry(dim: usize, len: usize) -> Result<(), String> {
+        if dim == 0 {
+            return Err("Tensor must have at least one dimension.".to_string());
+        }
+        if len < 2 {
+            return Err(format!("Invalid side length {}. Must be at least 2.", len));
+        }
+        if Self::cell_capacity(dim, len).is_none() {
+            return Err(format!("Tensor geometry L^d = {}^{} exceeds the 128-bit coordinate space.", len, dim));
+        }
+        Ok(())
+    }
+
+    pub fn pack(&self, coord: &[usize]) -> PackedCoord {
+        debug_assert_eq!(coord.len(), self.dimensions);
+        let l = self.side_length as u128;
+        PackedCoord(coord.iter().fold(0u128, |acc, &c| acc * l + c as u128))
+    }
+
+    pub fn unpack(&self, packed: PackedCoord) -> Coordinate {
+        let l = self.side_length as u128;
+        let mut coord = vec![0usize; self.dimensions];
+        let mut val = packed.0;
+        for slot in coord.iter_mut().rev() {
+            *slot = (val % l) as usize;
+            val /= l;
         }
         coord
     }
+
+    /// 前缀 coord[0..k] 覆盖的打包坐标区间 (连续)
+    pub fn prefix_range(&self, prefix: &[usize]) -> Range<PackedCoord> {
+        let l = self.side_length as u128;
+        let span = l.pow((self.dimensions - prefix.len()) as u32);
+        let base = prefix.iter().fold(0u128, |acc, &c| acc * l + c as u128);
+        PackedCoord(base * span)..PackedCoord((base + 1) * span)
+    }
+
+    pub fn contains(&self, coord: &Coordinate) -> bool {
+        self.data.contains(self.pack(coord))
+    }
+
+    /// 全部单元格的打包坐标区间
+    pub fn full_range(&self) -> Range<PackedCoord> {
+        self.prefix_range(&[])
+    }
+
+    pub fn map_id_to_coord(&self, numeric_id: u64) -> Coordinate {
+        index_to_coord(numeric_id, self.dimensions, self.side_length)
+    }
     
     pub fn map_id_to_coord_hash(&self, user_id: &str) -> Coordinate {
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(user_id.as_bytes());
-        hasher.update(b":htp:coord:v2");
-        let hash_output = hasher.finalize();
-        
-        // [SECURITY FIX]: 扩大寻址空间防止 Bucket Jamming (存储桶堵塞)
-        // 使用整个 128-bit (或更多) 来决定坐标，极大降低人为构造碰撞的风险
+        self.coord_from_address(self.member_address(user_id))
+    }
+
+    /// 用户 ID 的 128-bit 坐标地址 (与几何无关)：成员以注册时记录的地址为准 (可能经过探测改投)，
+    /// 非成员与未记录地址的旧成员取首个候选地址；顺序寻址的非成员取假序号 (见 Placement::dummy_index)
+    pub fn member_address(&self, user_id: &str) -> u128 {
+        match self.addresses.get(&member_digest(user_id)) {
+            Some(&address) => address,
+            None if self.addressing == AddressingMode::Sequential => self.placement.dummy_index(user_id, self.next_index) as u128,
+            None => self.placement.address(user_id, 0),
+        }
+    }
+
+    /// 顺序寻址时用户的逻辑序号 (证明中携带，验证方据此复算坐标)；哈希寻址返回 None
+    /// 非成员得到已分配区间内的假序号，假证明的坐标由该序号复算，证明结构不泄露成员资格
+    pub fn sequential_index(&self, user_id: &str) -> Option<u64> {
+        if self.addressing != AddressingMode::Sequential {
+            return None;
+        }
+        let capacity = Self::cell_capacity(self.dimensions, self.side_length)?;
+        Some((self.member_address(user_id) % capacity) as u64)
+    }
+
+    /// 为新成员选择地址 (planned 为本批已计划落入各单元格的人数)
+    /// 顺序寻址取下一个逻辑序号；哈希寻址沿候选序列取第一个未满的单元格
+    fn allocate(&self, user_id: &str, planned: &BTreeMap<PackedCoord, usize>) -> Result<(u128, PackedCoord), String> {
+        if self.addressing == AddressingMode::Sequential {
+            let index = self.next_index + planned.values().sum::<usize>() as u64;
+            if Self::cell_capacity(self.dimensions, self.side_length).is_some_and(|max| index as u128 >= max) {
+                return Err("Server Capacity Reached".to_string());
+            }
+            return Ok((index as u128, self.pack(&self.map_id_to_coord(index))));
+        }
+        let limit = self.placement.cell_limit(self.prime_config.bit_size);
+        for probe in 0..self.placement.probes() {
+            let address = self.placement.address(user_id, probe);
+            let key = self.pack(&self.coord_from_address(address));
+            let occupied = self.data.get(key)?.map_or(0, |cell| cell.members.len()) + planned.get(&key).copied().unwrap_or(0);
+            if occupied < limit {
+                return Ok((address, key));
+            }
+        }
+        Err(format!("Cell full: no cell with room for '{}' (limit {} members, policy {}).", user_id.escape_debug(), limit, self.placement.full_cell))
+    }
+
+    /// 地址按本张量的几何逐维取模
+    pub fn coord_from_address(&self, address: u128) -> Coordinate {
         let mut coord = Vec::with_capacity(self.dimensions);
-        let reader = hash_output.as_bytes();
         let l = self.side_length as u128;
-        
-        let mut val = u128::from_le_bytes(reader[0..16].try_into().unwrap());
-        
+        let mut val = address;
         for _ in 0..self.dimensions {
             coord.push((val % l) as usize);
             val /= l;
@@ -64,68 +279,707 @@ impl HyperTensor {
     }
 
     // [FIX]: 真正的碰撞处理 - 聚合写入 (Merge on Collision)
-    pub fn insert(&mut self, user_id: &str, new_tuple: AffineTuple) -> Result<(), String> {
-        // [SECURITY FIX]: 限制总桶数，防止 GMP OOM 导致进程 Abort
-        if self.data.len() > 10_000_000 {
+    // 新成员追加在桶尾 (depth = 当前成员数)，单元格值 = 按 depth 顺序的左折叠，与重放结果一致
+    // [FIX]: 默认幂等，同一 ID 重复注册不会再次合成 (否则根会变化且用户 "重复在场")
+    // nonce 为 new_tuple.p_factor 对应的 Hash-to-Prime nonce，随成员记录保存
+    pub fn insert(&mut self, user_id: &str, new_tuple: AffineTuple, nonce: u64) -> Result<InsertOutcome, String> {
+        self.insert_with_policy(user_id, new_tuple, nonce, RegistrationPolicy::default())
+    }
+
+    pub fn insert_with_policy(&mut self, user_id: &str, new_tuple: AffineTuple, nonce: u64, policy: RegistrationPolicy) -> Result<InsertOutcome, String> {
+        let digest = member_digest(user_id);
+        let address = self.member_address(user_id);
+        let key = self.pack(&self.coord_from_address(address));
+        // 已注册成员留在原单元格；新成员受占用上限约束
+        let (address, key) = match self.registered_depth(key, &digest)? {
+            Some(_) => (address, key),
+            None => self.allocate(user_id, &BTreeMap::new())?,
+        };
+        let member = CellMember {
+            id_digest: digest,
+            depth: 0,
+            prime: new_tuple.p_factor,
+            nonce,
+            q_shift: new_tuple.q_shift,
+        };
+        // [FIX]: 插入成功后才记录地址，失败的注册不占用地址或序号；
+        // WAL 中 Address 仍排在 Insert 之前：重分片追补时 Insert 之前已知其地址 (也为旧成员补录地址)
+        let mark = self.wal_buffer.len();
+        let outcome = self.insert_member(key, member, policy)?;
+        let ops = self.wal_buffer.split_off(mark);
+        self.record_address(digest, address);
+        self.wal_buffer.extend(ops);
+        Ok(outcome)
+    }
+
+    /// 记录成员地址；未变化时不产生 WAL 操作
+    pub fn record_address(&mut self, id_digest: [u8; 32], address: u128) {
+        if self.addresses.get(&id_digest) != Some(&address) {
+            self.note_address(id_digest, address);
+            self.wal_buffer.push(WalOp::Address { id_digest, address });
+        }
+    }
+
+    /// 写入地址表；顺序寻址时同步推进序号分配器 (WAL 重放因此无需单独记录分配器状态)
+    pub fn note_address(&mut self, id_digest: [u8; 32], address: u128) {
+        self.save_address(id_digest);
+        self.addresses.insert(id_digest, address);
+        if self.addressing == AddressingMode::Sequential {
+            self.next_index = self.next_index.max(address as u64 + 1);
+        }
+    }
+
+    /// 按 (单元格, 成员) 写入；WAL 重放与 insert_with_policy 共用此路径
+    pub fn insert_member(&mut self, key: PackedCoord, member: CellMember, policy: RegistrationPolicy) -> Result<InsertOutcome, String> {
+        // [SECURITY FIX]: 限制总桶数，防止 GMP OOM 导致进程 Abort (上限由存储后端决定)
+        if self.data.capacity().is_some_and(|max| self.data.len() > max) {
             return Err("Server Capacity Reached".to_string());
         }
 
-        let coord = self.map_id_to_coord_hash(user_id);
+        let digest = member.id_digest;
+        if let Some(existing) = self.registered_depth(key, &digest)? {
+            if policy == RegistrationPolicy::RejectDuplicates {
+                return Ok(InsertOutcome::AlreadyRegistered { depth: existing });
+            }
+            self.touch(key)?;
+            // 重新注册：从成员列表中移除旧记录并追加到桶尾，整桶重放
+            let mut bucket = self.data.get(key)?.ok_or("Membership index out of sync.")?.into_owned().members;
+            bucket.retain(|m| m.id_digest != digest);
+            bucket.push(member.clone());
+            let depth = (bucket.len() - 1) as u32;
+            self.rebuild_cell(key, bucket)?;
+            self.pending_changes += 1;
+            self.wal_buffer.push(WalOp::Insert { key, member, replace: true });
+            return Ok(InsertOutcome::Reregistered { depth });
+        }
         
-        if let Some(existing) = self.data.get(&coord) {
-            let merged = existing.compose(&new_tuple, &self.discriminant)?;
-            self.data.insert(coord.clone(), merged);
-        } else {
-            self.data.insert(coord, new_tuple);
+        self.touch(key)?;
+        let (value, mut bucket) = match self.data.get(key)? {
+            Some(existing) => {
+                let existing = existing.into_owned();
+                (existing.value.compose(&member.tuple(), &self.discriminant)?, existing.members)
+            },
+            None => (member.tuple(), Vec::new()),
+        };
+        let depth = bucket.len() as u32;
+        bucket.push(CellMember { depth, ..member.clone() });
+        self.data.put(key, CellRecord { value, members: bucket })?;
+
+        self.dirty.insert(key);
+        self.cached_root = None;
+        self.pending_changes += 1;
+        self.wal_buffer.push(WalOp::Insert { key, member, replace: false });
+        Ok(InsertOutcome::Inserted { depth })
+    }
+
+    /// [NEW FEATURE]: 成员撤销 - 从桶的有序成员列表中删除该用户并重放整个单元格，
+    /// 聚合索引随脏路径增量更新，下一次 commit_epoch 时推进 epoch。返回 false 表示该用户不在张量中。
+    pub fn revoke(&mut self, user_id: &str) -> Result<bool, String> {
+        let key = self.pack(&self.map_id_to_coord_hash(user_id));
+        self.revoke_member(key, member_digest(user_id))
+    }
+
+    pub fn revoke_member(&mut self, key: PackedCoord, id_digest: [u8; 32]) -> Result<bool, String> {
+        if self.registered_depth(key, &id_digest)?.is_none() {
+            return Ok(false);
         }
 
+        self.touch(key)?;
+        let mut bucket = self.data.get(key)?.ok_or("Membership index out of sync.")?.into_owned().members;
+        bucket.retain(|m| m.id_digest != id_digest);
+        self.rebuild_cell(key, bucket)?;
+        self.save_address(id_digest);
+        self.addresses.remove(&id_digest);
+        self.pending_changes += 1;
+        self.wal_buffer.push(WalOp::Revoke { key, id_digest });
+        Ok(true)
+    }
+
+    /// 取出自上次调用以来产生的 WAL 操作 (由持久化层追加到日志)
+    pub fn take_wal_ops(&mut self) -> Vec<WalOp> {
+        let ops = std::mem::take(&mut self.wal_buffer);
+        if let Some(tap) = self.reshard_tap.as_mut() {
+            tap.extend(ops.iter().cloned());
+        }
+        ops
+    }
+
+    /// 重放一条 WAL 操作；重放过程本身不再产生新的 WAL 操作
+    pub fn apply_wal_op(&mut self, op: WalOp) -> Result<(), String> {
+        match op {
+            WalOp::Insert { key, member, replace } => {
+                let policy = if replace { RegistrationPolicy::Reregister } else { RegistrationPolicy::RejectDuplicates };
+                self.insert_member(key, member, policy)?;
+            },
+            WalOp::Revoke { key, id_digest } => {
+                self.revoke_member(key, id_digest)?;
+            },
+            WalOp::Commit { epoch, timestamp } => {
+                self.commit_epoch_at(timestamp)?;
+                if self.epoch != epoch {
+                    return Err(format!("WAL replay diverged: expected epoch {}, reached {}.", epoch, self.epoch));
+                }
+            },
+            WalOp::Address { id_digest, address } => {
+                self.note_address(id_digest, address);
+            },
+        }
+        self.wal_buffer.clear();
+        Ok(())
+    }
+
+    /// 记录单元格在本 epoch 首次变更前的成员链，同时保存整条记录供回滚使用
+    fn touch(&mut self, key: PackedCoord) -> Result<(), String> {
+        if !self.pending_cells.contains_key(&key) {
+            self.begin_undo();
+            let record = self.data.get(key)?.map(|cell| cell.into_owned());
+            let before = record.as_ref()
+                .map(|cell| cell.members.iter().map(|m| m.tuple()).collect())
+                .unwrap_or_default();
+            self.undo.cells.insert(key, record);
+            self.pending_cells.insert(key, before);
+        }
+        Ok(())
+    }
+
+    /// 记录地址在本批次首次变更前的值
+    fn save_address(&mut self, id_digest: [u8; 32]) {
+        self.begin_undo();
+        let previous = self.addresses.get(&id_digest).copied();
+        self.undo.addresses.entry(id_digest).or_insert(previous);
+    }
+
+    fn begin_undo(&mut self) {
+        if !self.undo.active {
+            self.undo = UndoLog {
+                active: true,
+                next_index: self.next_index,
+                wal_len: self.wal_buffer.len(),
+                ..UndoLog::default()
+            };
+        }
+    }
+
+    /// [FIX]: 撤销自上次提交以来的全部变更，张量回到最近一次提交的状态
+    /// 变更或 commit_epoch 失败时调用；回滚本身失败说明存储后端已无法写入，调用方应停机
+    pub fn rollback(&mut self) -> Result<(), String> {
+        let undo = std::mem::take(&mut self.undo);
+        if !undo.active {
+            return Ok(());
+        }
+        for (key, record) in undo.cells {
+            match record {
+                Some(cell) => self.data.put(key, cell)?,
+                None if self.data.contains(key) => self.data.remove(key)?,
+                None => {},
+            }
+            self.dirty.insert(key);
+        }
+        for (id_digest, address) in undo.addresses {
+            match address {
+                Some(address) => self.addresses.insert(id_digest, address),
+                None => self.addresses.remove(&id_digest),
+            };
+        }
+        self.next_index = undo.next_index;
+        self.wal_buffer.truncate(undo.wal_len);
+        self.pending_cells.clear();
+        self.pending_changes = 0;
         self.cached_root = None;
         Ok(())
     }
+
+    /// 单元格当前的成员元组链 (按 depth 顺序)；空单元格返回空链
+    fn member_chain(&self, key: PackedCoord) -> Result<Vec<AffineTuple>, String> {
+        Ok(self.data.get(key)?
+            .map(|cell| cell.members.iter().map(|m| m.tuple()).collect())
+            .unwrap_or_default())
+    }
+
+    /// 提交当前批次的变更：epoch 递增，并将新根写入不可变历史
+    /// 没有待提交变更时不推进 epoch，返回 None
+    pub fn commit_epoch(&mut self) -> Result<Option<EpochRecord>, String> {
+        self.commit_epoch_at(unix_now())
+    }
+
+    /// 以给定时间戳提交 (WAL 重放时沿用原始提交时间，保证历史记录一致)
+    pub fn commit_epoch_at(&mut self, timestamp: u64) -> Result<Option<EpochRecord>, String> {
+        if self.pending_changes == 0 {
+            return Ok(None);
+        }
+        let root = self.calculate_global_root()?;
+        let delta = self.build_delta(self.epoch + 1)?;
+        let changed: Vec<Coordinate> = self.pending_cells.keys().map(|&k| self.unpack(k)).collect();
+        let update = EpochUpdate {
+            epoch: self.epoch + 1,
+            root: root.clone(),
+            nodes: self.path_nodes(&changed),
+            cells: changed.into_iter().zip(delta.cells.iter().map(|c| c.after.clone())).collect(),
+        };
+        self.pending_cells.clear();
+        self.undo = UndoLog::default();
+        self.journal.push_back(delta);
+        self.witness_updates.push_back(update);
+        self.epoch += 1;
+        let record = EpochRecord {
+            epoch: self.epoch,
+            root,
+            timestamp,
+            changes: self.pending_changes,
+        };
+        self.pending_changes = 0;
+        self.wal_buffer.push(WalOp::Commit { epoch: self.epoch, timestamp });
+
+        self.history.push_back(record.clone());
+        while self.history.len() > self.history_limit.max(1) {
+            self.history.pop_front();
+        }
+        while self.journal.len() > self.history_limit.max(1) {
+            self.journal.pop_front();
+        }
+        while self.witness_updates.len() > self.history_limit.max(1) {
+            self.witness_updates.pop_front();
+        }
+        Ok(Some(record))
+    }
+
+    /// 以变更单元格为分隔点，计算未变化区间的聚合 (查询聚合索引，无需全量折叠)
+    fn build_delta(&mut self, epoch: u64) -> Result<EpochDelta, String> {
+        let keys: Vec<PackedCoord> = self.pending_cells.keys().copied().collect();
+        let coords: Vec<Coordinate> = keys.iter().map(|&k| self.unpack(k)).collect();
+
+        // 聚合索引已由 calculate_global_root 刷新
+        let siblings = self.path_siblings(&coords);
+        let cells = keys.iter().zip(coords).map(|(&key, coord)| Ok(CellTransition {
+            coord,
+            before: self.pending_cells[&key].clone(),
+            after: self.member_chain(key)?,
+        })).collect::<Result<_, String>>()?;
+
+        Ok(EpochDelta { epoch, side_length: self.side_length, cells, siblings })
+    }
+
+    /// since_epoch 之后所有 epoch 的见证更新；所需数据已被淘汰时返回错误
+    pub fn updates_since(&self, since_epoch: u64) -> Result<Vec<EpochUpdate>, String> {
+        if since_epoch > self.epoch {
+            return Err(format!("Epoch {} is in the future (current {}).", since_epoch, self.epoch));
+        }
+        // 未保留任何更新 (如重分片之后) 时，早于当前 epoch 的见证同样需要重新获取
+        let first = self.witness_updates.front().map_or(self.epoch + 1, |u| u.epoch);
+        if since_epoch + 1 < first {
+            return Err(format!("Epoch {} is older than the retained updates (oldest {}). Re-fetch the proof.", since_epoch, first));
+        }
+        Ok(self.witness_updates.iter().filter(|u| u.epoch > since_epoch).cloned().collect())
+    }
+
+    /// epoch from → to 的一致性证明；摘要已被淘汰时返回错误
+    pub fn consistency_proof(&self, from: u64, to: u64) -> Result<ConsistencyProof, String> {
+        if from > to || to > self.epoch {
+            return Err(format!("Invalid epoch range {} -> {} (current {}).", from, to, self.epoch));
+        }
+        let first = match self.journal.front() {
+            Some(d) => d.epoch,
+            None => self.epoch + 1,
+        };
+        if from + 1 < first && from < to {
+            return Err(format!("Epoch {} is older than the retained journal (oldest delta {}).", from, first));
+        }
+        let deltas = self.journal.iter()
+            .filter(|d| d.epoch > from && d.epoch <= to)
+            .cloned()
+            .collect();
+        Ok(ConsistencyProof { from, to, deltas })
+    }
+
+    /// 查询历史 epoch 的根；已被淘汰或尚未提交的 epoch 返回 None
+    pub fn root_at(&self, epoch: u64) -> Option<&EpochRecord> {
+        let first = self.history.front()?.epoch;
+        let record = self.history.get(epoch.checked_sub(first)? as usize)?;
+        debug_assert_eq!(record.epoch, epoch);
+        Some(record)
+    }
+
+    /// [NEW FEATURE]: 批量注册 - 并行生成素数代表元，按单元格一次性合并，整批作为一个 epoch 提交
+    /// 同一单元格内的新成员按 ID 摘要升序追加，结果与批内顺序无关；
+    /// 批内重复与已注册的 ID 返回 AlreadyRegistered。返回值与输入顺序一致。
+    pub fn insert_batch<I, S>(&mut self, user_ids: I) -> Result<(Vec<InsertOutcome>, Option<EpochRecord>), String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let ids: Vec<S> = user_ids.into_iter().collect();
+        let pending = self.unregistered(&ids)?;
+        let primes = batch_primes(&self.prime_config, &pending)?;
+        self.insert_batch_with(&ids, &primes)
+    }
+
+    /// 批内尚未注册的 ID (保持输入顺序)；服务端在读锁下调用，据此在锁外生成代表元
+    pub fn unregistered<'a, S: AsRef<str>>(&self, ids: &'a [S]) -> Result<Vec<&'a str>, String> {
+        let mut pending = Vec::new();
+        for id in ids {
+            if !self.is_registered(id.as_ref())? {
+                pending.push(id.as_ref());
+            }
+        }
+        Ok(pending)
+    }
+
+    /// 批量注册的应用阶段：使用预先生成的代表元 (见 batch_primes)，只做地址分配与单元格合并
+    pub fn insert_batch_with<S: AsRef<str>>(&mut self, ids: &[S], primes: &BatchPrimes) -> Result<(Vec<InsertOutcome>, Option<EpochRecord>), String> {
+        if ids.len() > MAX_BATCH_SIZE {
+            return Err(format!("Batch too large: {} users (max {}).", ids.len(), MAX_BATCH_SIZE));
+        }
+        if self.data.capacity().is_some_and(|max| self.data.len() + ids.len() > max) {
+            return Err("Server Capacity Reached".to_string());
+        }
+
+        // 1. 去重并过滤已注册用户
+        let mut outcomes: Vec<Option<InsertOutcome>> = vec![None; ids.len()];
+        let mut seen: BTreeMap<[u8; 32], usize> = BTreeMap::new();
+        let mut fresh: Vec<(usize, PackedCoord, [u8; 32])> = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            let id = id.as_ref();
+            let key = self.pack(&self.map_id_to_coord_hash(id));
+            let digest = member_digest(id);
+            if let Some(depth) = self.registered_depth(key, &digest)? {
+                outcomes[i] = Some(InsertOutcome::AlreadyRegistered { depth });
+            } else if seen.insert(digest, i).is_none() {
+                fresh.push((i, key, digest));
+            }
+        }
+
+        // 按摘要顺序分配地址 (计入本批已计划的占用)，探测结果与批内顺序无关
+        fresh.sort_by_key(|&(_, _, digest)| digest);
+        let mut planned: BTreeMap<PackedCoord, usize> = BTreeMap::new();
+        let mut fresh_addresses: BTreeMap<[u8; 32], u128> = BTreeMap::new();
+        for (i, key, digest) in fresh.iter_mut() {
+            let (address, placed) = self.allocate(ids[*i].as_ref(), &planned)?;
+            *planned.entry(placed).or_default() += 1;
+            fresh_addresses.insert(*digest, address);
+            *key = placed;
+        }
+
+        // 2. 取出锁外生成的代表元；生成之后才被撤销的 ID 没有预生成的代表元，在此补算
+        let q_gen = ClassGroupElement::generator(&self.discriminant);
+
+        // 3. 按单元格分组，组内按摘要排序；先计算全部新单元格值，失败时张量保持不变
+        let mut per_cell: BTreeMap<PackedCoord, Vec<(usize, CellMember)>> = BTreeMap::new();
+        for (i, key, digest) in fresh {
+            let rep = match primes.get(&digest) {
+                Some(rep) => rep.clone(),
+                None => self.prime_config.hash_to_prime(ids[i].as_ref())?,
+            };
+            per_cell.entry(key).or_default().push((i, CellMember {
+                id_digest: digest,
+                depth: 0,
+                prime: rep.prime,
+                nonce: rep.nonce,
+                q_shift: q_gen.clone(),
+            }));
+        }
+        let mut merged_cells = Vec::with_capacity(per_cell.len());
+        for (key, mut newcomers) in per_cell {
+            newcomers.sort_by_key(|x| x.1.id_digest);
+            let (mut cell, bucket) = match self.data.get(key)? {
+                Some(existing) => {
+                    let existing = existing.into_owned();
+                    (Some(existing.value), existing.members)
+                },
+                None => (None, Vec::new()),
+            };
+            for (_, m) in &newcomers {
+                cell = combine(cell.as_ref(), Some(&m.tuple()), &self.discriminant)?;
+            }
+            merged_cells.push((key, cell, bucket, newcomers));
+        }
+
+        // 4. 应用：每个单元格只写入一次、标记一次脏路径
+        for (key, cell, mut bucket, newcomers) in merged_cells {
+            self.touch(key)?;
+            for (i, mut m) in newcomers {
+                m.depth = bucket.len() as u32;
+                outcomes[i] = Some(InsertOutcome::Inserted { depth: m.depth });
+                self.record_address(m.id_digest, fresh_addresses[&m.id_digest]);
+                self.wal_buffer.push(WalOp::Insert { key, member: m.clone(), replace: false });
+                bucket.push(m);
+                self.pending_changes += 1;
+            }
+            if let Some(value) = cell {
+                self.data.put(key, CellRecord { value, members: bucket })?;
+            }
+            self.dirty.insert(key);
+        }
+        self.cached_root = None;
+
+        // 批内重复：指向首次出现的结果
+        for (i, id) in ids.iter().enumerate() {
+            if outcomes[i].is_none() {
+                let first = seen[&member_digest(id.as_ref())];
+                let depth = match outcomes[first] {
+                    Some(InsertOutcome::Inserted { depth }) => depth,
+                    _ => 0,
+                };
+                outcomes[i] = Some(InsertOutcome::AlreadyRegistered { depth });
+            }
+        }
+
+        let record = self.commit_epoch()?;
+        Ok((outcomes.into_iter().flatten().collect(), record))
+    }
+
+    pub fn is_registered(&self, user_id: &str) -> Result<bool, String> {
+        let key = self.pack(&self.map_id_to_coord_hash(user_id));
+        Ok(self.registered_depth(key, &member_digest(user_id))?.is_some())
+    }
+
+    fn registered_depth(&self, key: PackedCoord, digest: &[u8; 32]) -> Result<Option<u32>, String> {
+        Ok(self.data.get(key)?.and_then(|cell| {
+            cell.members.iter().find(|m| &m.id_digest == digest).map(|m| m.depth)
+        }))
+    }
+
+    /// 按成员列表重新编号 depth 并重放单个单元格；空桶则删除该单元格
+    fn rebuild_cell(&mut self, key: PackedCoord, mut bucket: Vec<CellMember>) -> Result<(), String> {
+        for (i, m) in bucket.iter_mut().enumerate() {
+            m.depth = i as u32;
+        }
+        match fold_members(&bucket, &self.discriminant)? {
+            Some(value) => self.data.put(key, CellRecord { value, members: bucket })?,
+            None => self.data.remove(key)?,
+        }
+        self.dirty.insert(key);
+        self.cached_root = None;
+        Ok(())
+    }
+
+    /// 用户所在桶的有序成员链 (含自身) 及其 depth；非成员返回 None
+    pub fn bucket_chain(&self, user_id: &str) -> Result<Option<(u32, Vec<AffineTuple>)>, String> {
+        let key = self.pack(&self.map_id_to_coord_hash(user_id));
+        let digest = member_digest(user_id);
+        let cell = match self.data.get(key)? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        Ok(cell.members.iter()
+            .find(|m| m.id_digest == digest)
+            .map(|me| (me.depth, cell.members.iter().map(|m| m.tuple()).collect())))
+    }
+
+    /// 成员注册时记录的 Hash-to-Prime nonce；非成员返回 None
+    pub fn prime_nonce(&self, user_id: &str) -> Result<Option<u64>, String> {
+        let key = self.pack(&self.map_id_to_coord_hash(user_id));
+        let digest = member_digest(user_id);
+        Ok(self.data.get(key)?.and_then(|cell| {
+            cell.members.iter().find(|m| m.id_digest == digest).map(|m| m.nonce)
+        }))
+    }
+
+    /// 由成员列表重放所有单元格，丢弃现有单元格值并重建聚合
+    pub fn replay_members(&mut self) -> Result<(), String> {
+        let keys: Vec<PackedCoord> = self.data.keys(self.full_range()).collect();
+        for key in keys {
+            let bucket = self.data.get(key)?.ok_or("Cell vanished during replay.")?.into_owned().members;
+            self.rebuild_cell(key, bucket)?;
+        }
+        self.rebuild_aggregates();
+        Ok(())
+    }
+
+    /// 校验持久化的单元格值与成员列表重放结果逐位一致
+    pub fn verify_replay(&self) -> Result<(), String> {
+        for entry in self.data.range(self.full_range()) {
+            let (key, cell) = entry?;
+            let same = match fold_members(&cell.members, &self.discriminant)? {
+                Some(r) => r.p_factor == cell.value.p_factor && r.q_shift == cell.value.q_shift,
+                None => false,
+            };
+            if !same {
+                return Err(format!("Replay mismatch at cell {:?}.", self.unpack(key)));
+            }
+        }
+        Ok(())
+    }
     
+    /// [NEW FEATURE]: 冻结最新已提交 epoch 的只读副本 (聚合索引已刷新、根已缓存)
+    /// 单元格与聚合索引与活动张量结构共享，复制代价与张量规模无关；
+    /// 历史、一致性摘要与见证更新不随快照复制 (这些请求仍读活动张量)。
+    pub fn freeze(&mut self) -> Result<HyperTensor, String> {
+        if self.pending_changes > 0 {
+            return Err("Cannot freeze a tensor with uncommitted changes.".to_string());
+        }
+        let root = self.calculate_global_root()?;
+        Ok(HyperTensor {
+            dimensions: self.dimensions,
+            side_length: self.side_length,
+            discriminant: self.discriminant.clone(),
+            prime_config: self.prime_config.clone(),
+            data: self.data.freeze(),
+            epoch: self.epoch,
+            pending_changes: 0,
+            history: self.history.back().cloned().into_iter().collect(),
+            history_limit: self.history_limit,
+            pending_cells: BTreeMap::new(),
+            journal: VecDeque::new(),
+            wal_seq: self.wal_seq,
+            wal_buffer: Vec::new(),
+            witness_updates: VecDeque::new(),
+            addresses: self.addresses.clone(),
+            placement: self.placement.clone(),
+            addressing: self.addressing,
+            next_index: self.next_index,
+            reshard_tap: None,
+            cached_root: Some(root),
+            aggregates: self.aggregates.clone(),
+            dirty: BTreeSet::new(),
+            undo: UndoLog::default(),
+        })
+    }
+
     // [NEW FEATURE]: 持久化 - 保存到磁盘
+    // [FIX]: 原子写入 (临时文件 + fsync + rename)，崩溃时旧快照保持完整
+    // [NEW]: 自描述文件格式 (魔数、版本、参数指纹、epoch、根摘要、校验和)
     pub fn save_to_disk(&self, path: &str) -> Result<(), String> {
-        let file = File::create(path).map_err(|e| e.to_string())?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, self).map_err(|e| e.to_string())?;
-        Ok(())
+        let bytes = format::encode_tensor(self)?;
+        write_atomic(Path::new(path), &bytes)
     }
 
     // [NEW FEATURE]: 持久化 - 从磁盘加载
+    // 兼容旧版无文件头的原始布局
     pub fn load_from_disk(path: &str) -> Result<Self, String> {
-        let file = File::open(path).map_err(|e| e.to_string())?;
-        let reader = BufReader::new(file);
-        let tensor: HyperTensor = bincode::deserialize_from(reader).map_err(|e| e.to_string())?;
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let (mut tensor, _version) = format::decode_tensor(&bytes)?;
+        // 聚合索引不落盘，加载后按需重建
+        tensor.rebuild_aggregates();
         Ok(tensor)
     }
 
-    pub fn get_segment_tree_path(&self, coord: &Coordinate, _axis: usize) -> Vec<AffineTuple> {
-        let mut path = Vec::new();
-        if let Some(t) = self.data.get(coord) {
-            path.push(t.clone());
-        } else {
-            path.push(AffineTuple::identity(&self.discriminant));
+    pub fn get(&self, coord: &Coordinate) -> AffineTuple {
+        match self.data.get(self.pack(coord)) {
+            Ok(Some(cell)) => cell.value.clone(),
+            _ => AffineTuple::identity(&self.discriminant),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::testing::{discriminant, register};
+
+    fn commits(tensor: &HyperTensor) -> usize {
+        tensor.wal_buffer.iter().filter(|op| matches!(op, WalOp::Commit { .. })).count()
+    }
+
+    #[test]
+    fn history_is_trimmed_to_its_limit() {
+        let mut tensor = HyperTensor::new(2, 8, discriminant());
+        tensor.history_limit = 3;
+        let mut records = Vec::new();
+        for i in 0..5 {
+            register(&mut tensor, &format!("user-{}", i)).unwrap();
+            records.push(tensor.commit_epoch().unwrap().unwrap());
         }
-        // 模拟一层聚合以配合客户端验证
-        if self.side_length > 1 {
-             path.push(AffineTuple::identity(&self.discriminant));
+
+        assert_eq!(tensor.epoch, 5);
+        assert_eq!(tensor.history.iter().map(|r| r.epoch).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(tensor.journal.len(), 3);
+        assert_eq!(tensor.witness_updates.len(), 3);
+        for record in &records[2..] {
+            let kept = tensor.root_at(record.epoch).unwrap();
+            assert_eq!(kept.epoch, record.epoch);
+            assert!(kept.root.p_factor == record.root.p_factor && kept.root.q_shift == record.root.q_shift);
         }
-        path
     }
-    
-    pub fn get_orthogonal_anchors(&self, _coord: &Coordinate, axis: usize) -> Vec<AffineTuple> {
-        let mut anchors = Vec::new();
-        for dim in 0..self.dimensions {
-            if dim == axis { continue; }
-            anchors.push(AffineTuple::identity(&self.discriminant));
+
+    #[test]
+    fn root_at_rejects_evicted_and_future_epochs() {
+        let mut tensor = HyperTensor::new(2, 8, discriminant());
+        tensor.history_limit = 2;
+        for i in 0..4 {
+            register(&mut tensor, &format!("user-{}", i)).unwrap();
+            tensor.commit_epoch().unwrap().unwrap();
+        }
+
+        // 创世与 epoch 1、2 已被淘汰
+        for epoch in 0..3 {
+            assert!(tensor.root_at(epoch).is_none(), "epoch {}", epoch);
         }
-        anchors
+        assert!(tensor.root_at(3).is_some());
+        assert!(tensor.root_at(4).is_some());
+        // 尚未提交的 epoch
+        assert!(tensor.root_at(5).is_none());
+        assert!(tensor.root_at(u64::MAX).is_none());
     }
-    
-    pub fn get(&self, coord: &Coordinate) -> AffineTuple {
-        match self.data.get(coord) {
-            Some(tuple) => tuple.clone(),
-            None => AffineTuple::identity(&self.discriminant),
+
+    #[test]
+    fn commit_without_changes_keeps_the_epoch() {
+        let mut tensor = HyperTensor::new(2, 8, discriminant());
+        assert!(tensor.commit_epoch().unwrap().is_none());
+        assert_eq!((tensor.epoch, tensor.history.len(), commits(&tensor)), (0, 1, 0));
+
+        register(&mut tensor, "alice").unwrap();
+        assert_eq!(tensor.commit_epoch().unwrap().unwrap().epoch, 1);
+        assert_eq!(commits(&tensor), 1);
+
+        // 重复注册与撤销不存在的用户都不产生变更
+        assert!(matches!(register(&mut tensor, "alice").unwrap(), InsertOutcome::AlreadyRegistered { .. }));
+        assert!(!tensor.revoke("bob").unwrap());
+        assert_eq!(tensor.pending_changes, 0);
+        assert!(tensor.commit_epoch().unwrap().is_none());
+        assert_eq!((tensor.epoch, tensor.history.len(), tensor.journal.len(), commits(&tensor)), (1, 2, 1, 1));
+    }
+
+    fn same(x: &AffineTuple, y: &AffineTuple) -> bool {
+        x.p_factor == y.p_factor && x.q_shift == y.q_shift
+    }
+
+    #[test]
+    fn batch_result_is_independent_of_input_order() {
+        // 3x3 张量放 24 人，单元格内必然多人合成
+        let ids: Vec<String> = (0..24).map(|i| format!("user-{}", i)).collect();
+        let reversed: Vec<String> = ids.iter().rev().cloned().collect();
+
+        let mut forward = HyperTensor::new(2, 3, discriminant());
+        let (forward_outcomes, _) = forward.insert_batch(&ids).unwrap();
+        // 锁外预生成代表元 (服务端路径)，以及代表元缺失时的补算路径
+        let mut backward = HyperTensor::new(2, 3, discriminant());
+        let primes = batch_primes(&backward.prime_config, &reversed).unwrap();
+        let (backward_outcomes, _) = backward.insert_batch_with(&reversed, &primes).unwrap();
+        let mut fallback = HyperTensor::new(2, 3, discriminant());
+        fallback.insert_batch_with(&ids, &BatchPrimes::new()).unwrap();
+
+        let root = forward.calculate_global_root().unwrap();
+        assert!(same(&root, &backward.calculate_global_root().unwrap()));
+        assert!(same(&root, &fallback.calculate_global_root().unwrap()));
+        for (i, outcome) in forward_outcomes.iter().enumerate() {
+            assert_eq!(*outcome, backward_outcomes[ids.len() - 1 - i], "{}", ids[i]);
         }
+        assert_eq!((forward.epoch, backward.epoch), (1, 1));
+    }
+
+    #[test]
+    fn batch_reports_duplicates_and_registered_members() {
+        let mut tensor = HyperTensor::new(2, 8, discriminant());
+        let InsertOutcome::Inserted { depth: alice } = register(&mut tensor, "alice").unwrap() else {
+            panic!("alice is new");
+        };
+        tensor.commit_epoch().unwrap().unwrap();
+
+        let (outcomes, record) = tensor.insert_batch(["bob", "alice", "bob", "carol"]).unwrap();
+        let InsertOutcome::Inserted { depth: bob } = outcomes[0] else {
+            panic!("bob is new: {:?}", outcomes[0]);
+        };
+        assert_eq!(outcomes[1], InsertOutcome::AlreadyRegistered { depth: alice });
+        assert_eq!(outcomes[2], InsertOutcome::AlreadyRegistered { depth: bob });
+        assert!(matches!(outcomes[3], InsertOutcome::Inserted { .. }));
+        let record = record.unwrap();
+        assert_eq!((record.epoch, record.changes), (2, 2));
+        assert_eq!(tensor.bucket_chain("bob").unwrap().unwrap().0, bob);
+
+        // 全部已注册的批次不产生变更，也不推进 epoch
+        let (outcomes, record) = tensor.insert_batch(["carol", "alice", "carol"]).unwrap();
+        assert!(outcomes.iter().all(|o| matches!(o, InsertOutcome::AlreadyRegistered { .. })));
+        assert!(record.is_none());
+        assert_eq!(tensor.epoch, 2);
     }
 }
@@ -0,0 +1,19 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use rug::Integer;
+use crate::core::affine::AffineTuple;
+use crate::core::algebra::ClassGroupElement;
+use super::membership::InsertOutcome;
+use super::tensor::HyperTensor;
+
+/// 单元测试共用的 128-bit 判别式：运算快，不具备任何安全性
+pub fn discriminant() -> Integer {
+    Integer::from_str_radix("-170141183460469231731687303715884105851", 10).unwrap()
+}
+
+/// 按张量自身的 Hash-to-Prime 参数注册 user_id (不提交 epoch)
+pub fn register(tensor: &mut HyperTensor, user_id: &str) -> Result<InsertOutcome, String> {
+    let rep = tensor.prime_config.hash_to_prime(user_id)?;
+    let tuple = AffineTuple { p_factor: rep.prime, q_shift: ClassGroupElement::generator(&tensor.discriminant) };
+    tensor.insert(user_id, tuple, rep.nonce)
+}
@@ -0,0 +1,274 @@
+// COPYRIGHT (C) 2025 M-Patek. ALL RIGHTS RESERVED.
+
+use crate::core::affine::AffineTuple;
+use super::segment_tree::{combine, merge_nodes};
+use super::tensor::Coordinate;
+use rug::Integer;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// 聚合索引中的节点：第 level 维、前缀 coord[0..level] (混合进制打包)、堆下标 pos
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct NodeId {
+    pub level: u32,
+    pub prefix: u128,
+    pub pos: u64,
+}
+
+/// 前缀 coord[0..k] 的混合进制打包值
+pub fn pack_prefix(prefix: &[usize], side_length: usize) -> u128 {
+    prefix.iter().fold(0u128, |acc, &c| acc * side_length as u128 + c as u128)
+}
+
+/// 由若干单元格的值与其路径之外的兄弟节点重建全局根 (多路径 Merkle 证明)
+/// 坐标须维度一致且互不相同；逐维自底向上合并，路径上缺少的兄弟节点视为证明不完整。
+pub fn root_from_paths(
+    cells: &[(Coordinate, Option<AffineTuple>)],
+    siblings: &BTreeMap<NodeId, Option<AffineTuple>>,
+    side_length: usize,
+    discriminant: &Integer,
+) -> Result<AffineTuple, String> {
+    let dims = cells.first().map_or(0, |(c, _)| c.len());
+    if dims == 0 {
+        return Err("Proof contains no cells.".to_string());
+    }
+    let width = side_length.max(1).next_power_of_two();
+
+    // 当前维度上各子张量 (以前缀标识) 的值
+    let mut current: BTreeMap<Coordinate, Option<AffineTuple>> = BTreeMap::new();
+    for (coord, value) in cells {
+        if coord.len() != dims || coord.iter().any(|&c| c >= side_length) {
+            return Err(format!("Malformed proof: coordinate {:?} outside the {}^{} tensor.", coord, side_length, dims));
+        }
+        if current.insert(coord.clone(), value.clone()).is_some() {
+            return Err(format!("Malformed proof: cell {:?} listed twice.", coord));
+        }
+    }
+
+    for level in (0..dims).rev() {
+        let mut trees: BTreeMap<Coordinate, BTreeMap<usize, Option<AffineTuple>>> = BTreeMap::new();
+        for (coord, value) in current {
+            trees.entry(coord[..level].to_vec()).or_default().insert(width + coord[level], value);
+        }
+
+        current = BTreeMap::new();
+        for (prefix, mut known) in trees {
+            let packed = pack_prefix(&prefix, side_length);
+            // 按堆下标降序处理，子节点总是先于父节点
+            while let Some((pos, value)) = known.pop_last() {
+                if pos == 1 {
+                    current.insert(prefix, value);
+                    break;
+                }
+                let sibling = match known.remove(&(pos ^ 1)) {
+                    Some(v) => v,
+                    None => {
+                        let id = NodeId { level: level as u32, prefix: packed, pos: (pos ^ 1) as u64 };
+                        siblings.get(&id).cloned().ok_or_else(|| format!("Proof incomplete: missing sibling {:?}.", id))?
+                    },
+                };
+                let (left, right) = if pos & 1 == 0 { (value, sibling) } else { (sibling, value) };
+                let parent = merge_nodes(level, pos >> 1, left.as_ref(), right.as_ref(), discriminant)?;
+                known.insert(pos >> 1, parent);
+            }
+        }
+    }
+    Ok(current.remove(&Vec::new()).flatten().unwrap_or_else(|| AffineTuple::identity(discriminant)))
+}
+
+/// 成员见证：桶内成员链 + 沿各维线段树路径的兄弟节点
+/// 桶内成员链按序合成得到单元格值，再与兄弟节点逐层合并即可重建全局根 (Merkle 路径)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MembershipWitness {
+    pub epoch: u64,
+    pub coord: Coordinate,
+    pub side_length: usize,
+    /// 各维线段树的叶子宽度 (2 的幂)
+    pub width: usize,
+    pub depth: u32,
+    pub bucket_chain: Vec<AffineTuple>,
+    pub siblings: BTreeMap<NodeId, Option<AffineTuple>>,
+}
+
+impl MembershipWitness {
+    pub fn member(&self) -> Option<&AffineTuple> {
+        self.bucket_chain.get(self.depth as usize)
+    }
+
+    /// 由见证重建全局根
+    pub fn compute_root(&self, discriminant: &Integer) -> Result<AffineTuple, String> {
+        if self.width != self.side_length.max(1).next_power_of_two() {
+            return Err(format!("Malformed witness: width {} does not match side length {}.", self.width, self.side_length));
+        }
+        let mut value: Option<AffineTuple> = None;
+        for t in &self.bucket_chain {
+            value = combine(value.as_ref(), Some(t), discriminant)?;
+        }
+        root_from_paths(&[(self.coord.clone(), value)], &self.siblings, self.side_length, discriminant)
+    }
+}
+
+/// 单个 epoch 的见证更新数据：变更单元格的新成员链，以及聚合索引中所有被重算的节点
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EpochUpdate {
+    pub epoch: u64,
+    pub root: AffineTuple,
+    pub cells: Vec<(Coordinate, Vec<AffineTuple>)>,
+    pub nodes: Vec<(NodeId, Option<AffineTuple>)>,
+}
+
+fn same_tuple(x: &AffineTuple, y: &AffineTuple) -> bool {
+    x.p_factor == y.p_factor && x.q_shift == y.q_shift
+}
+
+/// 客户端本地见证存储：按 epoch 顺序应用更新，无需向服务器重新索取证明
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WitnessStore {
+    pub witnesses: HashMap<String, MembershipWitness>,
+}
+
+impl WitnessStore {
+    pub fn insert(&mut self, user_id: &str, witness: MembershipWitness) {
+        self.witnesses.insert(user_id.to_string(), witness);
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<&MembershipWitness> {
+        self.witnesses.get(user_id)
+    }
+
+    /// 所有见证中最旧的 epoch，作为 GetUpdates 的 since_epoch
+    pub fn epoch(&self) -> Option<u64> {
+        self.witnesses.values().map(|w| w.epoch).min()
+    }
+
+    /// 依次应用更新；每个 epoch 之后重建根并与更新中的根比对。
+    /// 成员已被撤销的见证会被移除，其 ID 作为返回值。
+    pub fn apply_updates(&mut self, updates: &[EpochUpdate], discriminant: &Integer) -> Result<Vec<String>, String> {
+        let mut revoked = Vec::new();
+        for (user_id, witness) in self.witnesses.iter_mut() {
+            let start = witness.epoch;
+            for update in updates.iter().filter(|u| u.epoch > start) {
+                if update.epoch != witness.epoch + 1 {
+                    return Err(format!("Update gap for '{}': witness at epoch {}, next update {}.", user_id, witness.epoch, update.epoch));
+                }
+
+                if let Some((_, chain)) = update.cells.iter().find(|(c, _)| c == &witness.coord) {
+                    let me = witness.member().cloned().ok_or("Witness corrupted: depth outside bucket chain.")?;
+                    match chain.iter().position(|t| same_tuple(t, &me)) {
+                        Some(depth) => {
+                            witness.depth = depth as u32;
+                            witness.bucket_chain = chain.clone();
+                        },
+                        None => {
+                            revoked.push(user_id.clone());
+                            break;
+                        },
+                    }
+                }
+
+                for (id, value) in &update.nodes {
+                    if let Some(slot) = witness.siblings.get_mut(id) {
+                        *slot = value.clone();
+                    }
+                }
+                witness.epoch = update.epoch;
+
+                if !same_tuple(&witness.compute_root(discriminant)?, &update.root) {
+                    return Err(format!("Witness for '{}' does not reproduce the root of epoch {}.", user_id, update.epoch));
+                }
+            }
+        }
+        for user_id in &revoked {
+            self.witnesses.remove(user_id);
+        }
+        Ok(revoked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::tensor::HyperTensor;
+    use crate::topology::testing::{discriminant, register};
+
+    fn tensor_with(members: usize) -> HyperTensor {
+        let mut tensor = HyperTensor::new(3, 5, discriminant());
+        for i in 0..members {
+            register(&mut tensor, &format!("user-{}", i)).unwrap();
+        }
+        tensor.commit_epoch().unwrap();
+        tensor
+    }
+
+    #[test]
+    fn witness_reproduces_the_root() {
+        let mut tensor = tensor_with(12);
+        let root = tensor.calculate_global_root().unwrap();
+        for i in 0..12 {
+            let witness = tensor.membership_witness(&format!("user-{}", i)).unwrap().unwrap();
+            let rebuilt = witness.compute_root(&tensor.discriminant).unwrap();
+            assert!(same_tuple(&rebuilt, &root));
+        }
+        assert!(tensor.membership_witness("stranger").unwrap().is_none());
+    }
+
+    #[test]
+    fn tampered_witness_does_not_reproduce_the_root() {
+        let mut tensor = tensor_with(12);
+        let root = tensor.calculate_global_root().unwrap();
+        let witness = tensor.membership_witness("user-0").unwrap().unwrap();
+        let d = &tensor.discriminant;
+
+        let mut moved = witness.clone();
+        moved.coord[0] = (moved.coord[0] + 1) % tensor.side_length;
+        assert!(moved.compute_root(d).map_or(true, |r| !same_tuple(&r, &root)));
+
+        let mut forged = witness.clone();
+        forged.bucket_chain[forged.depth as usize].p_factor += 2;
+        assert!(!same_tuple(&forged.compute_root(d).unwrap(), &root));
+
+        let mut incomplete = witness.clone();
+        let id = *incomplete.siblings.keys().next().unwrap();
+        incomplete.siblings.remove(&id);
+        assert!(incomplete.compute_root(d).is_err());
+
+        let mut malformed = witness;
+        malformed.width *= 2;
+        assert!(malformed.compute_root(d).is_err());
+    }
+
+    #[test]
+    fn updates_keep_witnesses_current() {
+        let mut tensor = tensor_with(12);
+        tensor.calculate_global_root().unwrap();
+        let mut store = WitnessStore::default();
+        for id in ["user-0", "user-1", "user-2"] {
+            store.insert(id, tensor.membership_witness(id).unwrap().unwrap());
+        }
+        let since = store.epoch().unwrap();
+
+        for i in 12..30 {
+            register(&mut tensor, &format!("user-{}", i)).unwrap();
+        }
+        tensor.commit_epoch().unwrap();
+        assert!(tensor.revoke("user-1").unwrap());
+        tensor.commit_epoch().unwrap();
+
+        let updates = tensor.updates_since(since).unwrap();
+        let revoked = store.apply_updates(&updates, &tensor.discriminant).unwrap();
+        assert_eq!(revoked, vec!["user-1".to_string()]);
+        assert!(store.get("user-1").is_none());
+
+        let root = tensor.calculate_global_root().unwrap();
+        for id in ["user-0", "user-2"] {
+            let witness = store.get(id).unwrap();
+            assert_eq!(witness.epoch, tensor.epoch);
+            assert!(same_tuple(&witness.compute_root(&tensor.discriminant).unwrap(), &root));
+        }
+
+        // 缺少中间 epoch 的更新
+        let mut stale = WitnessStore::default();
+        stale.insert("user-0", tensor_with(12).membership_witness("user-0").unwrap().unwrap());
+        assert!(stale.apply_updates(&updates[1..], &tensor.discriminant).is_err());
+    }
+}